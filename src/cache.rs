@@ -0,0 +1,109 @@
+//! class文件版本的磁盘缓存，重复运行时避免重新读取未变化的class文件
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use anyhow::{Result, Context};
+use tracing::warn;
+use serde::{Deserialize, Serialize};
+use crate::{read_class_file_version, JavaClassVersion};
+
+/// 缓存中的一项，文件大小或修改时间变化后失效
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheEntry {
+    size: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+    major: u16,
+    minor: u16,
+}
+
+/// 以class文件的绝对路径为键的版本缓存
+#[derive(Debug)]
+pub(crate) struct VersionCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    changed: AtomicBool,
+    hits: AtomicUsize,
+}
+
+impl VersionCache {
+    /// 读取缓存文件，文件不存在时返回空缓存，文件损坏时发出警告后返回空缓存
+    ///
+    /// 对应的class文件已不存在的条目在读取时丢弃。
+    pub(crate) fn load(path: &Path) -> Result<VersionCache> {
+        let mut entries: HashMap<String, CacheEntry> = if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("无法读取版本缓存: {:?}", path))?;
+            serde_json::from_str(&content).unwrap_or_else(|err| {
+                warn!("版本缓存 {:?} 格式无效，将重新生成: {}", path, err);
+                HashMap::new()
+            })
+        } else {
+            HashMap::new()
+        };
+        let count = entries.len();
+        entries.retain(|class_path, _| Path::new(class_path).exists());
+
+        Ok(VersionCache {
+            path: path.to_path_buf(),
+            changed: AtomicBool::new(entries.len() != count),
+            entries: Mutex::new(entries),
+            hits: AtomicUsize::new(0),
+        })
+    }
+
+    /// 读取class文件的版本，文件大小和修改时间与缓存一致时直接使用缓存
+    pub(crate) fn read_version(&self, class_file: &Path) -> Result<JavaClassVersion> {
+        let metadata = class_file.metadata()
+            .with_context(|| format!("无法获取文件元数据: {:?}", class_file))?;
+        let modified = metadata.modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let key = std::path::absolute(class_file)
+            .with_context(|| format!("无法解析路径: {:?}", class_file))?
+            .to_string_lossy()
+            .into_owned();
+
+        let cached = self.entries.lock().unwrap().get(&key).cloned();
+        if let Some(entry) = cached.filter(|entry| {
+            entry.size == metadata.len()
+                && entry.modified_secs == modified.as_secs()
+                && entry.modified_nanos == modified.subsec_nanos()
+        }) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(JavaClassVersion { major: entry.major, minor: entry.minor });
+        }
+
+        let version = read_class_file_version(class_file)?;
+        self.entries.lock().unwrap().insert(key, CacheEntry {
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            major: version.major,
+            minor: version.minor,
+        });
+        self.changed.store(true, Ordering::Relaxed);
+        Ok(version)
+    }
+
+    /// 命中缓存的次数
+    pub(crate) fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// 缓存有变化时写回缓存文件
+    pub(crate) fn save(&self) -> Result<()> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let json = serde_json::to_string(&*self.entries.lock().unwrap())
+            .context("序列化版本缓存失败")?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("写入版本缓存失败: {:?}", self.path))
+    }
+}
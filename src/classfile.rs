@@ -0,0 +1,464 @@
+//! class文件常量池解析
+
+use std::path::Path;
+use std::fs;
+use anyhow::{anyhow, Result, Context, bail};
+
+/// 类访问标志中表示编译器生成的类的位
+const ACC_SYNTHETIC: u16 = 0x1000;
+/// 类访问标志中表示模块描述（`module-info.class`）的位
+const ACC_MODULE: u16 = 0x8000;
+
+/// 常量池中的一项，只保留本工具关心的内容
+#[derive(Debug, Clone)]
+pub(crate) enum Constant {
+    Utf8(String),
+    Class(u16),
+    /// `CONSTANT_Module`，指向模块名的UTF-8常量
+    Module(u16),
+    /// 其他类型的常量，以及long/double占用的第二个槽位
+    Other,
+}
+
+/// 解析后的class文件头部（到this_class为止）
+#[derive(Debug)]
+pub(crate) struct ClassFile {
+    pub(crate) constant_pool: Vec<Constant>,
+    pub(crate) access_flags: u16,
+    pub(crate) this_class: u16,
+}
+
+/// 带边界检查的class文件读取器
+///
+/// 记录数据长度和当前偏移，读取超出数据末尾时返回错误而不是panic，
+/// 解析常量池等由文件内容决定长度的结构时都应通过它读取。
+#[derive(Debug, Clone)]
+pub(crate) struct ClassFileReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ClassFileReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> ClassFileReader<'a> {
+        ClassFileReader { bytes, position: 0 }
+    }
+
+    /// 当前偏移
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    /// 剩余未读取的字节数
+    pub(crate) fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// 是否已读取到数据末尾
+    pub(crate) fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// 已读取的字节
+    pub(crate) fn consumed(&self) -> &'a [u8] {
+        &self.bytes[..self.position]
+    }
+
+    /// 读取`length`个字节，剩余数据不足时返回错误，偏移保持不变
+    pub(crate) fn read_bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("class文件数据不完整: 在偏移 {} 处需要读取 {} 字节，只剩 {} 字节",
+                self.position, length, self.remaining()))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// 读取固定长度的字节数组
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.read_bytes(N)?;
+        Ok(bytes.try_into().expect("read_bytes返回的长度与请求一致"))
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+}
+
+/// 魔数不匹配时的提示，显示文件的前4个字节
+pub(crate) fn magic_mismatch_hint(magic: &[u8]) -> String {
+    let hex: Vec<String> = magic.iter().take(4).map(|byte| format!("{:02x}", byte)).collect();
+    format!("前4个字节为 {}，期望 ca fe ba be，可能不是Java class文件", hex.join(" "))
+}
+
+impl ClassFile {
+    /// 解析class文件字节
+    pub(crate) fn parse(bytes: &[u8]) -> Result<ClassFile> {
+        ClassFile::read(&mut ClassFileReader::new(bytes))
+    }
+
+    /// 从输入中读取到this_class为止的内容，输入停留在super_class处
+    fn read(input: &mut ClassFileReader) -> Result<ClassFile> {
+        let magic = input.read_u32()?;
+        if magic != 0xCAFEBABE {
+            bail!("无效的class文件格式，魔数不匹配（{}）", magic_mismatch_hint(&magic.to_be_bytes()));
+        }
+        // 跳过次版本号和主版本号
+        input.read_u32()?;
+
+        let constant_pool = read_constant_pool(input)?;
+        let access_flags = input.read_u16()?;
+        let this_class = input.read_u16()?;
+
+        Ok(ClassFile { constant_pool, access_flags, this_class })
+    }
+
+    /// 返回常量池中指定索引的UTF-8字符串
+    pub(crate) fn utf8(&self, index: u16) -> Result<&str> {
+        match self.constant_pool.get(index as usize) {
+            Some(Constant::Utf8(value)) => Ok(value),
+            _ => bail!("常量池索引 {} 不是UTF-8常量", index),
+        }
+    }
+
+    /// 类是否带有`ACC_SYNTHETIC`标志，即由编译器生成而非源代码中声明
+    pub(crate) fn is_synthetic(&self) -> bool {
+        self.access_flags & ACC_SYNTHETIC != 0
+    }
+
+    /// 是否为模块描述（`module-info.class`）
+    pub(crate) fn is_module(&self) -> bool {
+        self.access_flags & ACC_MODULE != 0
+    }
+
+    /// 返回当前类的全限定名（内部形式，如`com/example/Foo`）
+    pub(crate) fn class_name(&self) -> Result<&str> {
+        match self.constant_pool.get(self.this_class as usize) {
+            Some(Constant::Class(name_index)) => self.utf8(*name_index),
+            _ => bail!("this_class索引 {} 不是Class常量", self.this_class),
+        }
+    }
+}
+
+/// `--strip-debug`去除的调试属性
+const DEBUG_ATTRIBUTES: [&str; 4] = ["SourceFile", "LineNumberTable", "LocalVariableTable", "LocalVariableTypeTable"];
+
+/// 去除class文件中的调试属性，返回改写后的字节
+///
+/// 删除类的`SourceFile`属性以及方法`Code`属性中的`LineNumberTable`、`LocalVariableTable`和
+/// `LocalVariableTypeTable`，并改写相应的长度字段。文件头（包括版本号）和常量池原样保留，
+/// 不再被引用的常量不会删除。
+pub(crate) fn strip_debug_info(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut input = ClassFileReader::new(bytes);
+    let class_file = ClassFile::read(&mut input)?;
+    skip_interfaces(&mut input)?;
+    
+    // 文件头到接口列表为止原样保留
+    let mut output = input.consumed().to_vec();
+    
+    // 字段和方法
+    for _ in 0..2 {
+        let count = input.read_u16()?;
+        output.extend_from_slice(&count.to_be_bytes());
+        for _ in 0..count {
+            // access_flags、name_index、descriptor_index
+            output.extend_from_slice(input.read_bytes(6)?);
+            strip_attributes(&class_file, &mut input, &mut output)?;
+        }
+    }
+    strip_attributes(&class_file, &mut input, &mut output)?;
+    
+    if !input.is_empty() {
+        bail!("class文件末尾有多余的数据");
+    }
+    Ok(output)
+}
+
+/// 读取一个属性表，去除调试属性后写入`output`
+fn strip_attributes(class_file: &ClassFile, input: &mut ClassFileReader, output: &mut Vec<u8>) -> Result<()> {
+    let count = input.read_u16()?;
+    let mut kept = 0u16;
+    let mut attributes = Vec::new();
+    
+    for _ in 0..count {
+        let name_index = input.read_u16()?;
+        let length = input.read_u32()? as usize;
+        let info = input.read_bytes(length)?;
+        
+        let name = class_file.utf8(name_index)?;
+        if DEBUG_ATTRIBUTES.contains(&name) {
+            continue;
+        }
+        let info = if name == "Code" {
+            strip_code_attribute(class_file, info)?
+        } else {
+            info.to_vec()
+        };
+        
+        attributes.extend_from_slice(&name_index.to_be_bytes());
+        attributes.extend_from_slice(&(info.len() as u32).to_be_bytes());
+        attributes.extend_from_slice(&info);
+        kept += 1;
+    }
+    
+    output.extend_from_slice(&kept.to_be_bytes());
+    output.extend_from_slice(&attributes);
+    Ok(())
+}
+
+/// 读取class文件`SourceFile`属性记录的源文件名，没有该属性时返回None
+pub(crate) fn source_file(bytes: &[u8]) -> Result<Option<String>> {
+    let Some((class_file, info)) = class_attribute(bytes, "SourceFile")? else {
+        return Ok(None);
+    };
+    let source_index = ClassFileReader::new(info).read_u16()?;
+    Ok(Some(class_file.utf8(source_index)?.to_string()))
+}
+
+/// 读取模块描述`Module`属性中的模块名，不是模块描述时返回None
+pub(crate) fn module_name(bytes: &[u8]) -> Result<Option<String>> {
+    let Some((class_file, info)) = class_attribute(bytes, "Module")? else {
+        return Ok(None);
+    };
+    let module_index = ClassFileReader::new(info).read_u16()?;
+    match class_file.constant_pool.get(module_index as usize) {
+        Some(Constant::Module(name_index)) => Ok(Some(class_file.utf8(*name_index)?.to_string())),
+        _ => bail!("Module属性的索引 {} 不是Module常量", module_index),
+    }
+}
+
+/// 查找类的指定属性，返回解析后的文件头和属性内容，没有该属性时返回None
+fn class_attribute<'a>(bytes: &'a [u8], attribute_name: &str) -> Result<Option<(ClassFile, &'a [u8])>> {
+    let mut input = ClassFileReader::new(bytes);
+    let class_file = ClassFile::read(&mut input)?;
+    skip_interfaces(&mut input)?;
+    
+    // 跳过字段和方法
+    for _ in 0..2 {
+        let count = input.read_u16()?;
+        for _ in 0..count {
+            input.read_bytes(6)?;
+            let attributes_count = input.read_u16()?;
+            for _ in 0..attributes_count {
+                input.read_u16()?;
+                let length = input.read_u32()? as usize;
+                input.read_bytes(length)?;
+            }
+        }
+    }
+    
+    let attributes_count = input.read_u16()?;
+    for _ in 0..attributes_count {
+        let name_index = input.read_u16()?;
+        let length = input.read_u32()? as usize;
+        let info = input.read_bytes(length)?;
+        if class_file.utf8(name_index)? == attribute_name {
+            return Ok(Some((class_file, info)));
+        }
+    }
+    Ok(None)
+}
+
+/// 跳过super_class和接口列表
+fn skip_interfaces(input: &mut ClassFileReader) -> Result<()> {
+    input.read_u16()?;
+    let interfaces_count = input.read_u16()? as usize;
+    input.read_bytes(interfaces_count * 2)?;
+    Ok(())
+}
+
+/// 去除`Code`属性内嵌属性表中的调试属性
+fn strip_code_attribute(class_file: &ClassFile, info: &[u8]) -> Result<Vec<u8>> {
+    let mut input = ClassFileReader::new(info);
+    // max_stack、max_locals
+    input.read_bytes(4)?;
+    let code_length = input.read_u32()? as usize;
+    input.read_bytes(code_length)?;
+    let exception_table_length = input.read_u16()? as usize;
+    input.read_bytes(exception_table_length * 8)?;
+    
+    let mut output = input.consumed().to_vec();
+    strip_attributes(class_file, &mut input, &mut output)?;
+    if !input.is_empty() {
+        bail!("Code属性末尾有多余的数据");
+    }
+    Ok(output)
+}
+
+/// 每个常量池项至少占用的字节数（1字节tag加2字节内容）
+const MIN_CONSTANT_SIZE: usize = 3;
+
+/// 读取常量池，返回的列表下标与常量池索引一致（下标0未使用）
+///
+/// 先检查`constant_pool_count`与剩余数据长度是否相符，数量明显超出文件长度时直接报错。
+fn read_constant_pool(input: &mut ClassFileReader) -> Result<Vec<Constant>> {
+    let count_offset = input.position();
+    let count = input.read_u16()? as usize;
+    if count == 0 {
+        bail!("无效的常量池数量 0（偏移 {}）", count_offset);
+    }
+    if (count - 1) * MIN_CONSTANT_SIZE > input.remaining() {
+        bail!("常量池数量 {} 超出class文件剩余长度 {} 字节（偏移 {}），文件可能已损坏", count, input.remaining(), count_offset);
+    }
+    let mut pool = Vec::with_capacity(count);
+    pool.push(Constant::Other);
+
+    while pool.len() < count {
+        let tag = input.read_u8()?;
+        match tag {
+            // CONSTANT_Utf8
+            1 => {
+                let length = input.read_u16()? as usize;
+                let bytes = input.read_bytes(length)?;
+                pool.push(Constant::Utf8(String::from_utf8_lossy(bytes).into_owned()));
+            }
+            // CONSTANT_Integer, CONSTANT_Float
+            3 | 4 => {
+                input.read_bytes(4)?;
+                pool.push(Constant::Other);
+            }
+            // CONSTANT_Long, CONSTANT_Double 占用两个槽位
+            5 | 6 => {
+                input.read_bytes(8)?;
+                pool.push(Constant::Other);
+                pool.push(Constant::Other);
+            }
+            // CONSTANT_Class
+            7 => pool.push(Constant::Class(input.read_u16()?)),
+            // CONSTANT_Module
+            19 => pool.push(Constant::Module(input.read_u16()?)),
+            // CONSTANT_String, CONSTANT_MethodType, CONSTANT_Package
+            8 | 16 | 20 => {
+                input.read_bytes(2)?;
+                pool.push(Constant::Other);
+            }
+            // CONSTANT_Fieldref, CONSTANT_Methodref, CONSTANT_InterfaceMethodref,
+            // CONSTANT_NameAndType, CONSTANT_Dynamic, CONSTANT_InvokeDynamic
+            9 | 10 | 11 | 12 | 17 | 18 => {
+                input.read_bytes(4)?;
+                pool.push(Constant::Other);
+            }
+            // CONSTANT_MethodHandle
+            15 => {
+                input.read_bytes(3)?;
+                pool.push(Constant::Other);
+            }
+            _ => bail!("未知的常量池类型: {}（索引 {}）", tag, pool.len()),
+        }
+    }
+
+    Ok(pool)
+}
+
+/// 读取class文件中声明的全限定类名，如`com.example.Foo`
+pub fn read_class_name(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("无法读取class文件: {:?}", path))?;
+    let class_file = ClassFile::parse(&bytes)
+        .with_context(|| format!("无法解析class文件: {:?}", path))?;
+    Ok(class_file.class_name()?.replace('/', "."))
+}
+
+/// 判断class文件是否为编译器生成的合成类（带有`ACC_SYNTHETIC`标志）
+pub fn is_synthetic_class(path: &Path) -> Result<bool> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("无法读取class文件: {:?}", path))?;
+    let class_file = ClassFile::parse(&bytes)
+        .with_context(|| format!("无法解析class文件: {:?}", path))?;
+    Ok(class_file.is_synthetic())
+}
+
+/// 读取模块描述（`module-info.class`）中声明的模块名，如`com.example.app`，不是模块描述时返回None
+pub fn read_module_name(path: &Path) -> Result<Option<String>> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("无法读取class文件: {:?}", path))?;
+    let class_file = ClassFile::parse(&bytes)
+        .with_context(|| format!("无法解析class文件: {:?}", path))?;
+    if !class_file.is_module() {
+        return Ok(None);
+    }
+    module_name(&bytes)
+        .with_context(|| format!("无法解析class文件: {:?}", path))
+}
+
+/// 读取class文件`SourceFile`属性记录的源文件名（如`Foo.java`），编译时未保留该属性时返回None
+pub fn read_source_file(path: &Path) -> Result<Option<String>> {
+    let bytes = fs::read(path)
+        .with_context(|| format!("无法读取class文件: {:?}", path))?;
+    source_file(&bytes)
+        .with_context(|| format!("无法解析class文件: {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 魔数、版本号（JDK 8）和常量池数量
+    fn header(constant_pool_count: u16) -> Vec<u8> {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 52];
+        bytes.extend_from_slice(&constant_pool_count.to_be_bytes());
+        bytes
+    }
+
+    /// 只包含类名`Foo`的合成类
+    fn minimal_class() -> Vec<u8> {
+        let mut bytes = header(3);
+        bytes.extend_from_slice(&[1, 0, 3, b'F', b'o', b'o']);
+        bytes.extend_from_slice(&[7, 0, 1]);
+        bytes.extend_from_slice(&[0x10, 0x00, 0, 2]);
+        bytes
+    }
+
+    #[test]
+    fn reader_errors_instead_of_reading_past_end() {
+        let mut reader = ClassFileReader::new(&[0xCA, 0xFE, 0xBA]);
+        assert!(reader.read_u32().is_err());
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.read_u16().unwrap(), 0xCAFE);
+        assert!(reader.read_u16().is_err());
+        assert!(reader.read_bytes(usize::MAX).is_err());
+        assert_eq!(reader.read_u8().unwrap(), 0xBA);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn parses_minimal_class() {
+        let class_file = ClassFile::parse(&minimal_class()).unwrap();
+        assert_eq!(class_file.class_name().unwrap(), "Foo");
+        assert!(class_file.is_synthetic());
+    }
+
+    #[test]
+    fn constant_pool_count_beyond_file_length() {
+        let mut bytes = header(1000);
+        bytes.extend_from_slice(&[1, 0, 3, b'F', b'o', b'o']);
+        let err = ClassFile::parse(&bytes).unwrap_err();
+        assert!(err.to_string().contains("常量池数量 1000"), "{}", err);
+    }
+
+    #[test]
+    fn truncated_constant_pool_entry() {
+        let mut bytes = header(3);
+        // Utf8常量声明长度100，实际只有3个字节
+        bytes.extend_from_slice(&[1, 0, 100, b'F', b'o', b'o']);
+        let err = ClassFile::parse(&bytes).unwrap_err();
+        assert!(err.to_string().contains("class文件数据不完整"), "{}", err);
+    }
+
+    #[test]
+    fn every_truncation_of_a_valid_class_is_an_error() {
+        let bytes = minimal_class();
+        for length in 0..bytes.len() {
+            assert!(ClassFile::parse(&bytes[..length]).is_err(), "长度 {}", length);
+            assert!(source_file(&bytes[..length]).is_err(), "长度 {}", length);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! 比较两个输出目录，列出新增、删除和修改的文件以及class文件JDK版本的变化
+
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use anyhow::{bail, Result, Context};
+use walkdir::WalkDir;
+use crate::{file_sha256, format_size, jar, read_class_file_version};
+
+/// 两个目录的比较结果，路径均为以`/`分隔的相对路径
+#[derive(Debug, Default)]
+pub struct DirComparison {
+    /// 只存在于新目录中的文件
+    pub added: Vec<String>,
+    /// 只存在于旧目录中的文件
+    pub removed: Vec<String>,
+    /// 两个目录中都存在但内容不同的文件
+    pub changed: Vec<ChangedFile>,
+    /// 内容相同的文件数
+    pub unchanged: usize,
+}
+
+/// 内容不同的文件
+#[derive(Debug)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+    /// class文件的JDK版本，非class文件或无法读取时为None
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+}
+
+impl ChangedFile {
+    /// JDK版本是否变化
+    pub fn version_changed(&self) -> bool {
+        self.old_version != self.new_version
+    }
+}
+
+impl DirComparison {
+    /// 两个目录的文件是否完全相同
+    pub fn is_identical(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// JDK版本变化的class文件数
+    pub fn version_change_count(&self) -> usize {
+        self.changed.iter().filter(|file| file.version_changed()).count()
+    }
+
+    /// 打印比较结果：先列出每个有差异的文件，再打印各类文件的数量
+    pub fn print(&self) {
+        for path in &self.added {
+            println!("+ {}", path);
+        }
+        for path in &self.removed {
+            println!("- {}", path);
+        }
+        for file in &self.changed {
+            let mut detail = format!("{} -> {}", format_size(file.old_size), format_size(file.new_size));
+            if file.version_changed() {
+                detail.push_str(&format!("，{} -> {}",
+                    file.old_version.as_deref().unwrap_or("未知版本"),
+                    file.new_version.as_deref().unwrap_or("未知版本")));
+            }
+            println!("~ {}（{}）", file.path, detail);
+        }
+
+        println!("\n--- 比较结果 ---");
+        println!("新增: {}，删除: {}，修改: {}，未变化: {}",
+            self.added.len(), self.removed.len(), self.changed.len(), self.unchanged);
+        println!("JDK版本变化的class文件: {}", self.version_change_count());
+    }
+}
+
+/// 比较`old_dir`和`new_dir`中的所有文件：先比较大小，大小相同时比较SHA-256，class文件另外比较JDK版本
+pub fn compare_dirs(old_dir: &Path, new_dir: &Path) -> Result<DirComparison> {
+    let old_files = list_files(old_dir)?;
+    let mut new_files = list_files(new_dir)?;
+    let mut comparison = DirComparison::default();
+
+    for (name, old_path) in old_files {
+        let Some(new_path) = new_files.remove(&name) else {
+            comparison.removed.push(name);
+            continue;
+        };
+        let old_size = old_path.metadata()
+            .with_context(|| format!("无法读取文件信息: {:?}", old_path))?.len();
+        let new_size = new_path.metadata()
+            .with_context(|| format!("无法读取文件信息: {:?}", new_path))?.len();
+        if old_size == new_size && file_sha256(&old_path)? == file_sha256(&new_path)? {
+            comparison.unchanged += 1;
+            continue;
+        }
+
+        let is_class = name.ends_with(".class");
+        let version = |path: &Path| is_class
+            .then(|| read_class_file_version(path).ok())
+            .flatten()
+            .map(|version| version.to_jdk_version());
+        comparison.changed.push(ChangedFile {
+            old_version: version(&old_path),
+            new_version: version(&new_path),
+            path: name,
+            old_size,
+            new_size,
+        });
+    }
+    comparison.added = new_files.into_keys().collect();
+
+    Ok(comparison)
+}
+
+/// 列出目录下的所有文件，键为以`/`分隔的相对路径
+fn list_files(dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+    if !dir.is_dir() {
+        bail!("要比较的目录不存在: {:?}", dir);
+    }
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel_path = entry.path().strip_prefix(dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+            files.insert(jar::entry_name(rel_path), entry.path().to_path_buf());
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,58 @@
+//! 需要区分处理的错误类型，以及对应的进程退出码
+
+use std::io;
+use thiserror::Error;
+
+/// 可以被调用方区分的错误类型
+#[derive(Debug, Error)]
+pub enum Error {
+    /// 部分Java文件找不到对应的class文件
+    #[error("{count} 个Java文件找不到对应的class文件，操作取消")]
+    MissingClasses { count: usize },
+
+    /// class文件格式无效
+    #[error("{0}")]
+    MalformedClass(String),
+
+    /// 同一class文件存在于多个位置且内容不同
+    #[error("{count} 个class文件存在于多个位置且内容不同，请使用--prefer-first或--prefer-last选择，操作取消")]
+    ClassConflicts { count: usize },
+
+    /// 使用--keep-going时部分文件复制失败
+    #[error("{count} 个文件复制失败")]
+    CopyFailures { count: usize },
+}
+
+impl Error {
+    /// 该错误对应的进程退出码
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::MissingClasses { .. } => EXIT_MISSING_CLASSES,
+            Error::MalformedClass(_) => EXIT_MALFORMED_CLASS,
+            Error::CopyFailures { .. } => EXIT_IO,
+            Error::ClassConflicts { .. } => EXIT_FAILURE,
+        }
+    }
+}
+
+/// 其他错误的退出码
+pub const EXIT_FAILURE: i32 = 1;
+/// 部分Java文件找不到对应的class文件
+pub const EXIT_MISSING_CLASSES: i32 = 2;
+/// 读写文件失败
+pub const EXIT_IO: i32 = 3;
+/// class文件格式无效
+pub const EXIT_MALFORMED_CLASS: i32 = 4;
+
+/// 根据错误链确定进程退出码
+///
+/// 优先使用链上最外层的[`Error`]，其次是IO错误，都没有时返回[`EXIT_FAILURE`]。
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    if let Some(error) = err.chain().find_map(|cause| cause.downcast_ref::<Error>()) {
+        return error.exit_code();
+    }
+    if err.chain().any(|cause| cause.is::<io::Error>() || cause.is::<walkdir::Error>()) {
+        return EXIT_IO;
+    }
+    EXIT_FAILURE
+}
@@ -0,0 +1,281 @@
+//! jar/zip归档的读写
+
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use anyhow::{Result, Context};
+use walkdir::WalkDir;
+use filetime::FileTime;
+use zip::{ZipArchive, ZipWriter};
+use zip::write::SimpleFileOptions;
+use zip::CompressionMethod;
+
+/// jar清单文件在归档中的路径
+const MANIFEST_PATH: &str = "META-INF/MANIFEST.MF";
+
+/// 未提供清单文件时写入的最小清单
+const DEFAULT_MANIFEST: &str = "Manifest-Version: 1.0\r\nCreated-By: src_to_class\r\n\r\n";
+
+/// 将目录下的所有文件打包为jar，返回写入的条目数
+///
+/// 条目路径相对于`root_dir`。目录中没有`META-INF/MANIFEST.MF`时会生成一个最小清单。
+pub(crate) fn write_jar_from_dir(jar_path: &Path, root_dir: &Path) -> Result<usize> {
+    // jar文件本身位于目录中时（例如上次运行生成的），不能把它打包进去
+    let existing_jar = fs::canonicalize(jar_path).ok();
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(root_dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            if existing_jar.is_some() && fs::canonicalize(entry.path()).ok() == existing_jar {
+                continue;
+            }
+            let rel_path = entry.path().strip_prefix(root_dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+            entries.push((entry_name(rel_path), entry.path().to_path_buf()));
+        }
+    }
+    write_jar(jar_path, entries)
+}
+
+/// 将文件列表写入jar，列表中每一项为（归档内路径，磁盘文件路径），返回写入的条目数
+pub(crate) fn write_jar(jar_path: &Path, mut entries: Vec<(String, PathBuf)>) -> Result<usize> {
+    entries.sort();
+
+    if let Some(parent) = jar_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::File::create(jar_path)
+        .with_context(|| format!("无法创建jar文件: {:?}", jar_path))?;
+    let mut writer = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // 按照jar的惯例，清单文件是归档中的第一个条目
+    let mut count = 0;
+    if let Some(index) = entries.iter().position(|(name, _)| name == MANIFEST_PATH) {
+        let (name, path) = entries.remove(index);
+        write_entry(&mut writer, &name, &path, options)?;
+    } else {
+        writer.start_file(MANIFEST_PATH, options)?;
+        writer.write_all(DEFAULT_MANIFEST.as_bytes())?;
+    }
+    count += 1;
+
+    for (name, path) in &entries {
+        write_entry(&mut writer, name, path, options)?;
+        count += 1;
+    }
+
+    writer.finish()
+        .with_context(|| format!("写入jar文件失败: {:?}", jar_path))?;
+    Ok(count)
+}
+
+/// 按class文件所在包的前`depth`级分组，在`root_dir`下为每组生成一个jar（如`com.example.jar`），返回生成的jar及其条目数
+///
+/// 只打包class文件，不在任何包中的class文件放入`default.jar`。
+pub(crate) fn write_package_jars(root_dir: &Path, depth: usize) -> Result<Vec<(PathBuf, usize)>> {
+    let mut groups: BTreeMap<String, Vec<(String, PathBuf)>> = BTreeMap::new();
+    for entry in WalkDir::new(root_dir) {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.path().extension().is_none_or(|ext| ext != "class") {
+            continue;
+        }
+        let rel_path = entry.path().strip_prefix(root_dir)
+            .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+        let segments: Vec<_> = rel_path.parent()
+            .into_iter()
+            .flat_map(Path::components)
+            .take(depth)
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect();
+        let group = if segments.is_empty() { "default".to_string() } else { segments.join(".") };
+        groups.entry(group)
+            .or_default()
+            .push((entry_name(rel_path), entry.path().to_path_buf()));
+    }
+    
+    let mut jars = Vec::with_capacity(groups.len());
+    for (group, entries) in groups {
+        let jar_path = root_dir.join(format!("{}.jar", group));
+        let count = write_jar(&jar_path, entries)?;
+        jars.push((jar_path, count));
+    }
+    Ok(jars)
+}
+
+/// 用目录下的文件更新已有jar的结果
+#[derive(Debug, Default)]
+pub(crate) struct JarUpdate {
+    /// 新增的条目数
+    pub(crate) added: usize,
+    /// 内容变化而替换的条目数
+    pub(crate) replaced: usize,
+    /// 内容相同而保留的条目数
+    pub(crate) unchanged: usize,
+}
+
+/// 用目录下的文件更新已有的jar：替换内容不同的同名条目，追加新条目，其他条目原样保留
+///
+/// 先写入jar所在目录下的临时文件再替换原文件；没有新增或替换的条目时不改写jar。
+pub(crate) fn update_jar_from_dir(jar_path: &Path, root_dir: &Path) -> Result<JarUpdate> {
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(root_dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel_path = entry.path().strip_prefix(root_dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+            files.insert(entry_name(rel_path), entry.path().to_path_buf());
+        }
+    }
+
+    let file = fs::File::open(jar_path)
+        .with_context(|| format!("无法打开jar文件: {:?}", jar_path))?;
+    let mut archive = ZipArchive::new(file)
+        .with_context(|| format!("无法读取jar文件: {:?}", jar_path))?;
+    let parent = jar_path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(parent)
+        .with_context(|| format!("无法在 {:?} 中创建临时文件", parent))?;
+    let mut writer = ZipWriter::new(temp.reopen()?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut update = JarUpdate::default();
+    for index in 0..archive.len() {
+        let name = archive.name_for_index(index).unwrap_or_default().to_string();
+        if let Some(path) = files.remove(&name) {
+            if !entry_matches_file(&mut archive, index, &path)? {
+                write_entry(&mut writer, &name, &path, options)?;
+                update.replaced += 1;
+                continue;
+            }
+            update.unchanged += 1;
+        }
+        let entry = archive.by_index_raw(index)
+            .with_context(|| format!("无法读取jar条目: {}!{}", jar_path.display(), name))?;
+        writer.raw_copy_file(entry)
+            .with_context(|| format!("复制jar条目失败: {}", name))?;
+    }
+    for (name, path) in &files {
+        write_entry(&mut writer, name, path, options)?;
+        update.added += 1;
+    }
+    writer.finish()
+        .with_context(|| format!("写入jar文件失败: {:?}", temp.path()))?;
+
+    if update.added + update.replaced > 0 {
+        temp.persist(jar_path)
+            .with_context(|| format!("替换jar文件失败: {:?}", jar_path))?;
+    }
+    Ok(update)
+}
+
+/// 比较jar条目与磁盘文件的内容是否相同
+fn entry_matches_file(archive: &mut ZipArchive<fs::File>, index: usize, path: &Path) -> Result<bool> {
+    let mut entry = archive.by_index(index)?;
+    let bytes = fs::read(path)
+        .with_context(|| format!("无法读取文件: {:?}", path))?;
+    if entry.size() != bytes.len() as u64 {
+        return Ok(false);
+    }
+    let mut existing = Vec::with_capacity(bytes.len());
+    io::Read::read_to_end(&mut entry, &mut existing)
+        .with_context(|| format!("无法读取jar条目: {}", entry.name()))?;
+    Ok(existing == bytes)
+}
+
+/// 将磁盘上的一个文件写入归档
+fn write_entry<W: Write + io::Seek>(writer: &mut ZipWriter<W>, name: &str, path: &Path, options: SimpleFileOptions) -> Result<()> {
+    writer.start_file(name, options)
+        .with_context(|| format!("写入jar条目失败: {}", name))?;
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("无法打开文件: {:?}", path))?;
+    io::copy(&mut file, writer)
+        .with_context(|| format!("写入jar条目失败: {}", name))?;
+    Ok(())
+}
+
+/// 作为class路径使用的jar/zip归档
+///
+/// 打开时只读取条目列表，找到的class文件按需提取到`extract_dir`下，之后与class目录中的文件一样处理。
+pub(crate) struct ClassPathJar {
+    /// jar文件路径
+    pub(crate) path: PathBuf,
+    /// 提取条目的目录
+    pub(crate) extract_dir: PathBuf,
+    entries: Vec<String>,
+}
+
+impl ClassPathJar {
+    pub(crate) fn open(path: &Path, extract_dir: PathBuf) -> Result<ClassPathJar> {
+        let file = fs::File::open(path)
+            .with_context(|| format!("无法打开jar文件: {:?}", path))?;
+        let archive = ZipArchive::new(file)
+            .with_context(|| format!("无法读取jar文件: {:?}", path))?;
+        let entries = archive.file_names()
+            .filter(|name| name.ends_with(".class"))
+            .map(String::from)
+            .collect();
+        Ok(ClassPathJar {
+            path: path.to_path_buf(),
+            extract_dir,
+            entries,
+        })
+    }
+    
+    /// 查找包目录下满足`matches`的class条目，`matches`的参数为不含扩展名的文件名
+    pub(crate) fn find_entries(&self, package_path: &Path, matches: impl Fn(&str) -> bool) -> Vec<&str> {
+        let package = entry_name(package_path);
+        self.entries.iter()
+            .filter(|name| {
+                let file_name = match name.rsplit_once('/') {
+                    Some((parent, file_name)) if parent == package => file_name,
+                    None if package.is_empty() => name.as_str(),
+                    _ => return false,
+                };
+                file_name.strip_suffix(".class").is_some_and(&matches)
+            })
+            .map(String::as_str)
+            .collect()
+    }
+    
+    /// 将条目提取到`extract_dir`下，返回提取后相对于`extract_dir`的路径
+    ///
+    /// 提取的文件使用jar文件的修改时间，使增量复制的判断不受提取时间影响。
+    pub(crate) fn extract(&self, names: &[&str]) -> Result<Vec<PathBuf>> {
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+        let file = fs::File::open(&self.path)
+            .with_context(|| format!("无法打开jar文件: {:?}", self.path))?;
+        let mut archive = ZipArchive::new(file)
+            .with_context(|| format!("无法读取jar文件: {:?}", self.path))?;
+        let modified = FileTime::from_last_modification_time(&fs::metadata(&self.path)?);
+        
+        let mut rel_paths = Vec::new();
+        for name in names {
+            let rel_path: PathBuf = name.split('/').collect();
+            let target = self.extract_dir.join(&rel_path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut entry = archive.by_name(name)
+                .with_context(|| format!("无法读取jar条目: {}!{}", self.path.display(), name))?;
+            let mut output = fs::File::create(&target)
+                .with_context(|| format!("无法创建文件: {:?}", target))?;
+            io::copy(&mut entry, &mut output)
+                .with_context(|| format!("提取jar条目失败: {}!{}", self.path.display(), name))?;
+            filetime::set_file_mtime(&target, modified)
+                .with_context(|| format!("无法设置文件修改时间: {:?}", target))?;
+            rel_paths.push(rel_path);
+        }
+        Ok(rel_paths)
+    }
+}
+
+/// 将相对路径转换为归档内使用`/`分隔的路径
+pub(crate) fn entry_name(rel_path: &Path) -> String {
+    rel_path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,152 @@
+//! Java（以及Kotlin、Scala）源文件的轻量扫描，不做完整的语法解析
+
+use std::path::Path;
+use std::fs;
+use anyhow::{Result, Context};
+
+/// Java中声明类型的关键字
+const TYPE_KEYWORDS: [&str; 4] = ["class", "interface", "enum", "record"];
+
+/// Kotlin中声明类型的关键字（`enum class`、`data class`等修饰词后仍跟着`class`）
+const KOTLIN_TYPE_KEYWORDS: [&str; 3] = ["class", "interface", "object"];
+
+/// Scala中声明类型的关键字
+const SCALA_TYPE_KEYWORDS: [&str; 3] = ["class", "trait", "object"];
+
+/// 读取源文件中声明的所有顶层类型名（类、接口、枚举、注解和记录）
+///
+/// 按扩展名选择关键字：`.kt`文件识别`object`，`.scala`文件识别`trait`和`object`，其他文件按Java处理。
+pub fn read_top_level_types(java_file: &Path) -> Result<Vec<String>> {
+    let bytes = fs::read(java_file)
+        .with_context(|| format!("无法读取源文件: {:?}", java_file))?;
+    let source = String::from_utf8_lossy(&bytes);
+    let keywords: &[&str] = match java_file.extension().and_then(|ext| ext.to_str()) {
+        Some("kt") => &KOTLIN_TYPE_KEYWORDS,
+        Some("scala") => &SCALA_TYPE_KEYWORDS,
+        _ => &TYPE_KEYWORDS,
+    };
+    Ok(top_level_types(&source, keywords))
+}
+
+/// 读取源文件中`package`声明的包名（如`com.example`），没有声明时返回None
+pub fn read_package(java_file: &Path) -> Result<Option<String>> {
+    let bytes = fs::read(java_file)
+        .with_context(|| format!("无法读取源文件: {:?}", java_file))?;
+    Ok(declared_package(&String::from_utf8_lossy(&bytes)))
+}
+
+/// 扫描源码中第一个`package`声明，Java以分号结尾，Kotlin、Scala可以省略分号
+pub(crate) fn declared_package(source: &str) -> Option<String> {
+    let tokens = tokenize(&strip_comments_and_literals(source));
+    let start = tokens.iter().position(|token| token == "package")? + 1;
+    let mut package = String::new();
+    let mut rest = tokens[start..].iter();
+    loop {
+        let name = rest.next().filter(|name| is_identifier(name))?;
+        package.push_str(name);
+        if rest.next().map(String::as_str) != Some(".") {
+            break;
+        }
+        package.push('.');
+    }
+    Some(package)
+}
+
+/// 扫描源码中紧跟在`keywords`之后的所有顶层类型名
+pub(crate) fn top_level_types(source: &str, keywords: &[&str]) -> Vec<String> {
+    let tokens = tokenize(&strip_comments_and_literals(source));
+    let mut names = Vec::new();
+    let mut depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        match token.as_str() {
+            "{" => depth += 1,
+            "}" => depth = depth.saturating_sub(1),
+            keyword if depth == 0 && keywords.contains(&keyword) => {
+                // 跳过`Foo.class`这类字面量以及包名中的同名片段
+                if i > 0 && tokens[i - 1] == "." {
+                    continue;
+                }
+                if let Some(name) = tokens.get(i + 1).filter(|name| is_identifier(name)) {
+                    if !keywords.contains(&name.as_str()) && !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    names
+}
+
+/// 将注释、字符串和字符字面量替换为空格，保留其余代码
+fn strip_comments_and_literals(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut result = String::with_capacity(source.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let rest = &chars[i..];
+        if rest.starts_with(&['/', '/']) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if rest.starts_with(&['/', '*']) {
+            i += 2;
+            while i < chars.len() && !chars[i..].starts_with(&['*', '/']) {
+                i += 1;
+            }
+            i += 2;
+        } else if rest.starts_with(&['"', '"', '"']) {
+            // 文本块
+            i += 3;
+            while i < chars.len() && !chars[i..].starts_with(&['"', '"', '"']) {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 3;
+        } else if chars[i] == '"' || chars[i] == '\'' {
+            let quote = chars[i];
+            i += 1;
+            while i < chars.len() && chars[i] != quote && chars[i] != '\n' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+        } else {
+            result.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        result.push(' ');
+    }
+
+    result
+}
+
+/// 将代码拆分为标识符和单个符号
+fn tokenize(code: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+
+    for c in code.chars() {
+        if c.is_alphanumeric() || c == '_' || c == '$' {
+            current.push(c);
+            continue;
+        }
+        if !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+        if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+fn is_identifier(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+}
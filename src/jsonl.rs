@@ -0,0 +1,69 @@
+//! 逐个文件写入的JSON Lines报告，适用于文件数量很多的运行
+
+use std::path::Path;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::sync::Mutex;
+use anyhow::{Result, Context};
+use serde::Serialize;
+use crate::{ClassReport, FileReport, VersionReport};
+
+/// JSON Lines报告中的一行，对应一个复制的文件
+#[derive(Debug, Serialize)]
+struct JsonlRecord<'a> {
+    /// 文件类型：`class`、`non_java`、`resource`或`source`
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'a str>,
+    path: &'a str,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<&'a VersionReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<&'a str>,
+}
+
+/// 每复制一个文件写入一行JSON，每行写入后立即刷新，运行期间即可读取已写入的部分
+#[derive(Debug)]
+pub(crate) struct JsonlReport {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl JsonlReport {
+    /// 创建报告文件，已存在时清空
+    pub(crate) fn create(path: &Path) -> Result<JsonlReport> {
+        let file = File::create(path)
+            .with_context(|| format!("无法创建JSON Lines报告: {:?}", path))?;
+        Ok(JsonlReport { writer: Mutex::new(LineWriter::new(file)) })
+    }
+
+    /// 写入一个复制的class文件
+    pub(crate) fn write_class(&self, source: &str, class: &ClassReport) -> Result<()> {
+        self.write(&JsonlRecord {
+            kind: "class",
+            source: Some(source),
+            path: &class.path,
+            size: class.size,
+            version: class.version.as_ref(),
+            sha256: class.sha256.as_deref(),
+        })
+    }
+
+    /// 写入一个复制的非class文件，`kind`为`non_java`、`resource`或`source`
+    pub(crate) fn write_file(&self, kind: &'static str, file: &FileReport) -> Result<()> {
+        self.write(&JsonlRecord {
+            kind,
+            source: None,
+            path: &file.path,
+            size: file.size,
+            version: None,
+            sha256: file.sha256.as_deref(),
+        })
+    }
+
+    fn write(&self, record: &JsonlRecord) -> Result<()> {
+        let line = serde_json::to_string(record).context("序列化JSON Lines记录失败")?;
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", line).context("写入JSON Lines报告失败")
+    }
+}
@@ -0,0 +1,3908 @@
+//! 根据Java源文件查找对应的class文件并复制到指定目录。
+//!
+//! 命令行工具`src_to_class`是对[`run`]的简单封装，其他Rust工具也可以直接调用本库。
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::io::Read;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use walkdir::WalkDir;
+use anyhow::{Result, Context, bail};
+use tracing::{debug_span, error, info, instrument, warn};
+use serde::{Deserialize, Serialize};
+use rayon::prelude::*;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use filetime::FileTime;
+use std::io::IsTerminal;
+use sha2::{Digest, Sha256};
+use owo_colors::OwoColorize;
+
+mod cache;
+mod classfile;
+mod compare;
+mod error;
+mod jar;
+mod javasource;
+mod jsonl;
+mod progress;
+mod project;
+mod warning;
+mod watch;
+
+pub use classfile::{is_synthetic_class, read_class_name, read_module_name, read_source_file};
+pub use compare::{compare_dirs, ChangedFile, DirComparison};
+pub use error::{Error, exit_code, EXIT_FAILURE, EXIT_MISSING_CLASSES, EXIT_IO, EXIT_MALFORMED_CLASS};
+pub use javasource::{read_package, read_top_level_types};
+pub use project::{detect_project_layout, BuildSystem, ProjectLayout};
+pub use warning::{Warning, WarningKind, print_warnings};
+pub use watch::watch;
+
+/// 复制选项
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// 仅打印将要执行的复制操作，不实际创建目录或复制文件
+    pub dry_run: bool,
+    /// 将复制结果以JSON格式写入指定文件
+    pub report: Option<PathBuf>,
+    /// 将源文件到class文件的对应关系以文本格式写入指定文件
+    pub manifest: Option<PathBuf>,
+    /// 将每个复制的文件的路径、大小、SHA-256和JDK版本以CSV格式写入指定文件，供合规工具读取
+    pub inventory: Option<PathBuf>,
+    /// 期望的输出文件列表（每行一个相对路径），复制完成后与输出目录中的文件比较，不一致时报错
+    pub expect: Option<PathBuf>,
+    /// 并行复制class文件的线程数，None表示使用CPU核心数；同时作为`read_jobs`和`copy_jobs`的默认值
+    pub jobs: Option<usize>,
+    /// 并行读取class文件版本的线程数，None时使用`jobs`
+    pub read_jobs: Option<usize>,
+    /// 并行复制文件的线程数，None时使用`jobs`
+    pub copy_jobs: Option<usize>,
+    /// 允许的最高JDK版本
+    pub target_jdk: Option<u16>,
+    /// 只处理匹配这些glob模式的源文件
+    pub includes: Vec<String>,
+    /// 排除匹配这些glob模式的源文件
+    pub excludes: Vec<String>,
+    /// 在输出目录中为源文件创建硬链接代替复制，失败时改为复制
+    pub hardlink: bool,
+    /// 在输出目录中创建指向源文件（绝对路径）的符号链接代替复制
+    pub symlink: bool,
+    /// 复制后保留源文件的修改时间
+    pub preserve_timestamps: bool,
+    /// 计算每个复制文件的SHA-256
+    pub checksums: bool,
+    /// 始终打印每个文件的详细信息，不显示进度条
+    pub verbose: bool,
+    /// 不打印每个文件的详细信息和分隔线，只保留汇总信息、错误和警告
+    pub quiet: bool,
+    /// 只复制指定JDK版本编译的class文件，其他版本的class文件跳过
+    pub only_jdk: Option<u16>,
+    /// 复制完成后将输出目录打包为jar文件
+    pub jar: Option<PathBuf>,
+    /// 复制完成后按包名的前N级分组，在输出目录中为每组class文件生成一个jar
+    pub jar_per_package: Option<usize>,
+    /// 复制完成后用输出目录中的文件更新已有的jar文件（替换或新增条目，保留其他条目）
+    pub update_jar: Option<PathBuf>,
+    /// 生成jar时不保留输出目录中的文件
+    pub no_output_dir: bool,
+    /// 判断目标文件是否未变化时比较文件内容的SHA-256，而不是修改时间
+    pub verify_hash: bool,
+    /// 遍历源代码目录和class目录时跟随符号链接
+    pub follow_symlinks: bool,
+    /// 报告class目录中没有对应源文件的class文件
+    pub report_orphans: bool,
+    /// 复制前检查是否有目标路径只有大小写不同，存在时报错
+    pub case_check: bool,
+    /// 在class目录之后按顺序查找的jar/zip文件
+    pub classpath_jars: Vec<PathBuf>,
+    /// 允许写入非空的输出目录
+    pub force: bool,
+    /// 写入前清空输出目录中的已有内容（需要同时指定`force`）
+    pub clean: bool,
+    /// 文件大小显示为精确的字节数，而不是KiB/MiB
+    pub bytes: bool,
+    /// 除`java`外也作为源文件处理的扩展名，如`kt`、`scala`
+    pub source_exts: Vec<String>,
+    /// 复制文件遇到暂时性IO错误时的最大重试次数
+    pub copy_retries: u32,
+    /// 第一次重试前等待的毫秒数，之后每次重试加倍
+    pub retry_delay_ms: u64,
+    /// 复制文件时使用该大小（字节）的缓冲区逐块读写，None时使用`fs::copy`
+    pub buffer_size: Option<usize>,
+    /// 复制class文件时去除行号表、局部变量表和源文件名等调试信息
+    pub strip_debug: bool,
+    /// 复制完成后以树形结构打印输出目录
+    pub tree: bool,
+    /// class文件版本的缓存文件
+    pub version_cache: Option<PathBuf>,
+    /// 复制时重定位包目录的规则，格式为`from=to`（如`com.old=com.new`）
+    pub map_packages: Vec<String>,
+    /// 将警告以JSON格式写入指定文件
+    pub warnings_file: Option<PathBuf>,
+    /// 同时复制class目录中没有对应源文件的资源文件（非class文件）
+    pub include_class_resources: bool,
+    /// class文件在输出目录中的布局
+    pub output_layout: OutputLayout,
+    /// 检查class文件的SourceFile属性是否与源文件名一致
+    pub verify_source_attr: bool,
+    /// 从该文件（`-`表示标准输入）读取源文件列表，代替遍历源代码目录
+    pub source_list: Option<PathBuf>,
+    /// 从该JSON文件读取源文件到class文件的对应关系，不再遍历源代码目录或查找class文件
+    pub mapping: Option<PathBuf>,
+    /// 匹配class文件名时忽略ASCII大小写，用于大小写不敏感的文件系统
+    pub ignore_case: bool,
+    /// 在每个class文件的输出行中显示从常量池读取的全限定类名
+    pub show_class_names: bool,
+    /// 遍历源代码目录的最大深度，None表示不限制
+    pub source_max_depth: Option<usize>,
+    /// 在包目录下查找class文件的最大深度，None表示1（只查找包目录本身）
+    pub class_max_depth: Option<usize>,
+    /// 只复制修改时间不早于该时刻的class文件
+    pub since: Option<SystemTime>,
+    /// 单个文件复制失败时记录错误并继续处理其他文件，而不是立即中止
+    pub keep_going: bool,
+    /// 按多版本jar的规则，从class目录的`META-INF/versions/N`中选择不高于该版本的最高版本class文件
+    pub multi_release: Option<u16>,
+    /// `regex=replacement`形式的class文件重命名规则，作用于外部类名
+    pub renames: Vec<String>,
+    /// 平均每个源文件的class文件数低于该值时发出警告，可能只编译了部分源文件
+    pub min_classes_per_source: Option<f64>,
+    /// 每复制一个文件就向该文件追加一行JSON
+    pub report_jsonl: Option<PathBuf>,
+    /// 跳过带有`ACC_SYNTHETIC`标志的编译器生成类
+    pub skip_synthetic: bool,
+    /// 只复制与源文件中声明的类型同名的class文件，不复制`$`内部类
+    pub no_inner_classes: bool,
+    /// 根据class文件版本、预览标志和SourceFile属性推测每个class文件的编译器
+    pub guess_compiler: bool,
+    /// class目录中的文件在输出目录中的路径相对于该目录计算，而不是相对于各class目录
+    pub class_relative_to: Option<PathBuf>,
+    /// 找不到class文件的源文件只发出警告并跳过，而不是中止操作
+    pub allow_missing: bool,
+    /// 不复制源代码目录中的非Java文件，只输出class文件
+    pub no_resources: bool,
+    /// 同时把每个源文件复制到输出目录中与源代码目录相同的相对路径
+    pub copy_sources: bool,
+    /// 按JDK版本分目录输出class文件（如`jdk8/`），非class文件放在`resources/`下
+    pub bucket_by_version: bool,
+    /// 检测到多个不同的JDK版本时报错，而不只是警告
+    pub fail_on_mixed_versions: bool,
+    /// 多个class目录或class路径jar中存在内容不同的同一class文件时的处理方式
+    pub duplicate_policy: DuplicatePolicy,
+    /// 按源文件中的`package`声明而不是源文件所在目录确定在class目录中查找的包目录
+    pub derive_package_from_source: bool,
+    /// 只打印每个源文件对应的class文件，不读取class文件版本，也不复制文件
+    pub list_only: bool,
+    /// 只并行读取所有class文件的版本并统计，不复制文件
+    pub audit: bool,
+    /// 只检查每个源文件都能找到class文件且版本不超过`target_jdk`，不使用输出目录也不复制文件
+    pub verify_only: bool,
+    /// 先复制到输出目录旁的临时目录，全部完成后再整体替换输出目录，不能与`clean`同时使用
+    pub atomic: bool,
+    /// 只复制这些类（全限定名，如`com.example.Foo`）及其内部类的class文件，为空时不限制
+    pub classes: Vec<String>,
+    /// 跳过格式无效的class文件（如魔数不匹配），而不是中止操作
+    pub skip_invalid_class: bool,
+    /// 复制文本资源文件时统一转换为该换行符
+    pub normalize_eol: Option<LineEnding>,
+    /// 转换换行符的文本文件扩展名，为空时使用[`DEFAULT_TEXT_EXTENSIONS`]
+    pub text_exts: Vec<String>,
+}
+
+impl Options {
+    /// 读取class文件版本的线程池，线程数为0时使用CPU核心数
+    fn read_pool(&self) -> Result<rayon::ThreadPool> {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.read_jobs.or(self.jobs).unwrap_or(0))
+            .thread_name(|index| format!("read-{}", index))
+            .build()
+            .context("创建读取线程池失败")
+    }
+    
+    /// 复制文件的线程池，线程数为0时使用CPU核心数
+    fn copy_pool(&self) -> Result<rayon::ThreadPool> {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.copy_jobs.or(self.jobs).unwrap_or(0))
+            .thread_name(|index| format!("copy-{}", index))
+            .build()
+            .context("创建复制线程池失败")
+    }
+    
+    /// 查找源文件和class文件时使用的选项
+    fn lookup_options(&self) -> LookupOptions {
+        LookupOptions {
+            follow_links: self.follow_symlinks,
+            source_exts: self.source_exts.clone(),
+            ignore_case: self.ignore_case,
+            source_max_depth: self.source_max_depth,
+            class_max_depth: self.class_max_depth,
+            multi_release: self.multi_release,
+            duplicate_policy: self.duplicate_policy,
+        }
+    }
+    
+    /// 按选项格式化文件大小
+    fn display_size(&self, size: u64) -> String {
+        if self.bytes {
+            format!("{} 字节", size)
+        } else {
+            format_size(size)
+        }
+    }
+    
+    /// 每行输出的前缀，演练模式下为`[DRY-RUN] `
+    fn output_prefix(&self) -> &'static str {
+        if self.dry_run { "[DRY-RUN] " } else { "" }
+    }
+}
+
+/// class文件在输出目录中的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputLayout {
+    /// 保留包目录结构
+    #[default]
+    Package,
+    /// 所有class文件直接放在输出目录下
+    Flat,
+}
+
+/// 多个class目录或class路径jar中存在同一class文件（相对路径相同）时的处理方式
+///
+/// 内容相同的文件总是只复制一次，该选项只决定内容不同时的行为。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// 内容不同时报错
+    #[default]
+    Error,
+    /// 内容不同时使用先找到的文件
+    PreferFirst,
+    /// 内容不同时使用后找到的文件
+    PreferLast,
+}
+
+impl std::str::FromStr for OutputLayout {
+    type Err = String;
+    
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "package" => Ok(OutputLayout::Package),
+            "flat" => Ok(OutputLayout::Flat),
+            _ => Err(format!("无效的输出布局: {}，可选值为 package、flat", value)),
+        }
+    }
+}
+
+/// 换行符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+    
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(format!("无效的换行符: {}，可选值为 lf、crlf", value)),
+        }
+    }
+}
+
+/// 默认转换换行符的文本文件扩展名
+pub const DEFAULT_TEXT_EXTENSIONS: [&str; 16] = [
+    "properties", "xml", "txt", "json", "yml", "yaml", "sql", "html",
+    "htm", "css", "js", "csv", "md", "conf", "ini", "MF",
+];
+
+/// 查找源文件和class文件的选项
+#[derive(Debug, Clone, Default)]
+pub struct LookupOptions {
+    /// 遍历目录时跟随符号链接，符号链接形成循环时返回错误
+    pub follow_links: bool,
+    /// 除`java`外也作为源文件处理的扩展名，如`kt`、`scala`
+    pub source_exts: Vec<String>,
+    /// 匹配class文件名时忽略ASCII大小写
+    pub ignore_case: bool,
+    /// 遍历源代码目录的最大深度（直接位于源代码目录下的文件为1），None表示不限制
+    pub source_max_depth: Option<usize>,
+    /// 在包目录下查找class文件的最大深度（直接位于包目录下的文件为1），None表示1
+    pub class_max_depth: Option<usize>,
+    /// 同时查找class目录的`META-INF/versions/N`（N不高于该值），优先使用版本最高的class文件
+    pub multi_release: Option<u16>,
+    /// 多个位置中存在内容不同的同一class文件时的处理方式
+    pub duplicate_policy: DuplicatePolicy,
+}
+
+/// 在多个class目录或class路径jar中找到同一class文件的统计
+#[derive(Debug, Default)]
+pub struct Duplicates {
+    /// 内容相同、只复制一次的重复文件数
+    pub deduped: usize,
+    /// 内容不同的class文件（相对路径）
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl LookupOptions {
+    /// 判断文件是否为需要查找class文件的源文件
+    pub fn is_source_file(&self, path: &Path) -> bool {
+        path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+            ext == "java" || self.source_exts.iter().any(|source_ext| source_ext.trim_start_matches('.') == ext)
+        })
+    }
+}
+
+/// 复制操作的汇总信息
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// 每个源文件及其复制的class文件
+    pub sources: Vec<SourceReport>,
+    /// 复制的非Java文件
+    pub non_java_files: Vec<FileReport>,
+    /// 从class目录复制的资源文件
+    pub class_resources: Vec<FileReport>,
+    /// 使用--copy-sources时复制的源文件
+    pub copied_sources: Vec<FileReport>,
+    pub source_count: usize,
+    pub class_count: usize,
+    /// 为源文件找到的class文件总数，包括因--only-jdk、--since跳过的class文件
+    pub found_class_count: usize,
+    pub non_java_count: usize,
+    pub class_resource_count: usize,
+    /// 使用--copy-sources时复制的源文件数
+    pub copied_source_count: usize,
+    /// 目标文件未变化而跳过复制的文件数
+    pub unchanged_count: usize,
+    /// 使用预览特性编译的class文件数
+    pub preview_count: usize,
+    /// 因JDK版本不符合--only-jdk而跳过的class文件数
+    pub version_skipped_count: usize,
+    /// 修改时间早于--since而跳过的class文件数
+    pub since_skipped_count: usize,
+    /// 因--skip-synthetic跳过的合成类数
+    pub synthetic_skipped_count: usize,
+    /// 因--no-inner-classes跳过的内部类数
+    pub inner_skipped_count: usize,
+    /// 因--skip-invalid-class跳过的无效class文件数
+    pub invalid_skipped_count: usize,
+    /// 使用--allow-missing时因找不到class文件而跳过的源文件数
+    pub missing_source_count: usize,
+    /// 所有class文件的修改时间都早于--since、视为未变化的源文件数
+    pub unchanged_source_count: usize,
+    /// 存在于多个位置且内容相同、只复制一次的class文件数
+    pub deduped_count: usize,
+    /// 存在于多个位置且内容不同、按--prefer-first/--prefer-last选择的class文件数
+    pub conflict_count: usize,
+    /// 没有对应源文件的class文件（相对于class目录），未要求检查时为None
+    pub orphan_classes: Option<Vec<PathBuf>>,
+    /// 每个JDK版本对应的class文件
+    pub jdk_versions: BTreeMap<String, Vec<PathBuf>>,
+    /// 推测的编译器及其编译的class文件数，未使用--guess-compiler时为None
+    pub compilers: Option<BTreeMap<String, usize>>,
+    /// 复制过程中收集的警告
+    pub warnings: Vec<Warning>,
+    /// 使用`keep_going`时复制失败的文件，不为空时调用方应以非零状态退出
+    pub failures: Vec<FileFailure>,
+    /// 各阶段的耗时
+    pub timings: Timings,
+}
+
+/// 各阶段的耗时
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Timings {
+    /// 收集源文件
+    pub collect: Duration,
+    /// 为源文件查找class文件，以及查找孤立class文件和资源文件
+    pub resolve: Duration,
+    /// 复制文件
+    pub copy: Duration,
+    /// 整个操作，包括写入报告和打包jar
+    pub total: Duration,
+}
+
+impl Summary {
+    /// 所有class文件的组合SHA-256，未计算校验和时为None
+    ///
+    /// 按相对路径排序后，对`<sha256>  <相对路径>`形式的行计算SHA-256，便于比较两次运行的结果。
+    pub fn class_sha256(&self) -> Option<String> {
+        let mut classes: Vec<(&str, &str)> = self.sources.iter()
+            .flat_map(|source| &source.classes)
+            .map(|class| Some((class.path.as_str(), class.sha256.as_deref()?)))
+            .collect::<Option<_>>()?;
+        if classes.is_empty() {
+            return None;
+        }
+        classes.sort();
+        
+        let mut hasher = Sha256::new();
+        for (path, sha256) in classes {
+            hasher.update(format!("{}  {}\n", sha256, path).as_bytes());
+        }
+        Some(to_hex(&hasher.finalize()))
+    }
+    
+    /// 平均每个源文件找到的class文件数，没有源文件时为None
+    pub fn classes_per_source(&self) -> Option<f64> {
+        (self.source_count > 0).then(|| self.found_class_count as f64 / self.source_count as f64)
+    }
+    
+    /// 复制文件总数
+    pub fn total_copied(&self) -> usize {
+        self.class_count + self.non_java_count + self.class_resource_count + self.copied_source_count
+    }
+    
+    /// 复制文件的总字节数
+    pub fn total_bytes(&self) -> u64 {
+        let class_bytes: u64 = self.sources.iter()
+            .flat_map(|source| &source.classes)
+            .map(|class| class.size)
+            .sum();
+        class_bytes + self.non_java_files.iter()
+            .chain(&self.class_resources)
+            .chain(&self.copied_sources)
+            .map(|file| file.size)
+            .sum::<u64>()
+    }
+    
+    /// 将汇总信息序列化为单行JSON，包含各项计数和每个JDK版本的class文件数
+    pub fn to_json(&self) -> Result<String> {
+        let summary = SummaryJson {
+            source_count: self.source_count,
+            class_count: self.class_count,
+            non_java_count: self.non_java_count,
+            total_copied: self.total_copied(),
+            jdk_versions: self.jdk_versions.iter()
+                .map(|(version, files)| (version.as_str(), files.len()))
+                .collect(),
+        };
+        serde_json::to_string(&summary).context("序列化汇总信息失败")
+    }
+    
+    /// 打印各阶段的耗时
+    pub fn print_timings(&self) {
+        println!("\n-- 各阶段耗时 --");
+        println!("收集: {}, 解析: {}, 复制: {}",
+            format_duration(self.timings.collect),
+            format_duration(self.timings.resolve),
+            format_duration(self.timings.copy)
+        );
+    }
+    
+    /// 以水平条形图打印各JDK版本的class文件数，按主版本号排序，条形长度按终端宽度缩放
+    pub fn print_histogram(&self) {
+        let width = console::Term::stdout().size_checked()
+            .map_or(HISTOGRAM_DEFAULT_WIDTH, |(_, columns)| columns as usize);
+        println!("\n-- JDK版本分布 --");
+        print!("{}", self.render_histogram(width));
+    }
+    
+    /// 按`width`列宽渲染JDK版本条形图，每个版本一行
+    fn render_histogram(&self, width: usize) -> String {
+        let mut versions: Vec<(&str, usize)> = self.jdk_versions.iter()
+            .map(|(version, files)| (version.as_str(), files.len()))
+            .collect();
+        versions.sort_by_key(|(version, _)| (major_of_jdk_version(version).unwrap_or(u16::MAX), *version));
+        
+        let total: usize = versions.iter().map(|(_, count)| count).sum();
+        let max_count = versions.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let label_width = versions.iter()
+            .map(|(version, _)| console::measure_text_width(version))
+            .max()
+            .unwrap_or(0);
+        let count_width = max_count.to_string().len();
+        // 版本名、计数和百分比之外的宽度用于条形
+        let bar_width = width.saturating_sub(label_width + count_width + 12).max(10);
+        
+        let mut output = String::new();
+        for (version, count) in versions {
+            let bar_len = (count * bar_width).div_ceil(max_count.max(1));
+            let padding = label_width - console::measure_text_width(version);
+            output.push_str(&format!("{}{} {} {:>count_width$} ({:.1}%)\n",
+                version,
+                " ".repeat(padding),
+                "█".repeat(bar_len),
+                count,
+                count as f64 * 100.0 / total as f64
+            ));
+        }
+        output
+    }
+    
+    /// 按class文件所在包的前`depth`级汇总复制的class文件数和总大小，按包名排序打印
+    pub fn print_group_report(&self, depth: usize) {
+        let mut groups: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+        for class in self.sources.iter().flat_map(|source| &source.classes) {
+            let package_dir = Path::new(&class.path).parent().unwrap_or(Path::new(""));
+            let package: Vec<_> = package_dir.components()
+                .take(depth)
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            let group = groups.entry(package.join(".")).or_default();
+            group.0 += 1;
+            group.1 += class.size;
+        }
+        
+        let rows: Vec<(&str, String, String)> = groups.iter()
+            .map(|(package, (count, size))| (display_package(package), count.to_string(), format_size(*size)))
+            .collect();
+        let package_width = rows.iter()
+            .map(|(package, _, _)| console::measure_text_width(package))
+            .chain(std::iter::once(console::measure_text_width("包")))
+            .max()
+            .unwrap_or(0);
+        let count_width = rows.iter()
+            .map(|(_, count, _)| count.len())
+            .chain(std::iter::once(console::measure_text_width("class文件数")))
+            .max()
+            .unwrap_or(0);
+        
+        println!("\n-- 按包统计（前 {} 级） --", depth);
+        let pad = |text: &str, width: usize| format!("{}{}", text, " ".repeat(width - console::measure_text_width(text)));
+        println!("{}  {}  大小", pad("包", package_width), pad("class文件数", count_width));
+        for (package, count, size) in &rows {
+            println!("{}  {:>count_width$}  {}", pad(package, package_width), count, size);
+        }
+    }
+    
+    /// 打印`--audit`的版本统计
+    pub fn print_audit(&self) {
+        println!("\n--- 版本统计 ---");
+        println!("源文件总数: {}", self.source_count);
+        println!("class文件总数: {}", self.found_class_count);
+        if self.missing_source_count > 0 {
+            println!("找不到class文件的源文件: {}", self.missing_source_count);
+        }
+        println!("总耗时: {}", format_duration(self.timings.total));
+        self.print_jdk_versions();
+    }
+    
+    /// 打印各JDK版本的class文件数，只有一个版本时打印一行
+    fn print_jdk_versions(&self) {
+        if self.jdk_versions.len() > 1 {
+            println!("\n-- 不同JDK版本文件统计 --");
+            for (version, files) in &self.jdk_versions {
+                println!("{}: {} 个文件", version, files.len());
+            }
+        } else if let Some(version) = self.jdk_versions.keys().next() {
+            println!("所有文件JDK版本: {}", version);
+        }
+    }
+    
+    /// 打印汇总信息
+    pub fn print(&self) {
+        println!("\n--- 汇总信息 ---");
+        println!("源文件总数: {}", self.source_count);
+        println!("class文件总数: {}", self.class_count);
+        if let Some(ratio) = self.classes_per_source() {
+            println!("平均每个源文件的class文件数: {:.2}", ratio);
+        }
+        println!("非Java文件总数: {}", self.non_java_count);
+        if self.class_resource_count > 0 {
+            println!("class目录资源文件总数: {}", self.class_resource_count);
+        }
+        if self.copied_source_count > 0 {
+            println!("复制的源文件总数: {}", self.copied_source_count);
+        }
+        println!("复制文件总计: {}", self.total_copied());
+        println!("文件总大小: {} ({} 字节)", format_size(self.total_bytes()), self.total_bytes());
+        println!("总耗时: {}", format_duration(self.timings.total));
+        if let Some(class_sha256) = self.class_sha256() {
+            println!("class文件组合SHA-256: {}", class_sha256);
+        }
+        if self.version_skipped_count > 0 {
+            println!("因JDK版本不符跳过的class文件: {}", self.version_skipped_count);
+        }
+        if self.since_skipped_count > 0 {
+            println!("早于指定时间跳过的class文件: {}，未变化的源文件: {}", self.since_skipped_count, self.unchanged_source_count);
+        }
+        if self.synthetic_skipped_count > 0 {
+            println!("跳过的合成类: {}", self.synthetic_skipped_count);
+        }
+        if self.inner_skipped_count > 0 {
+            println!("跳过的内部类: {}", self.inner_skipped_count);
+        }
+        if self.invalid_skipped_count > 0 {
+            println!("跳过的无效class文件: {}", self.invalid_skipped_count);
+        }
+        if self.missing_source_count > 0 {
+            println!("因找不到class文件跳过的源文件: {}", self.missing_source_count);
+        }
+        if self.unchanged_count > 0 {
+            println!("实际复制: {}，未变化跳过: {}", self.total_copied() - self.unchanged_count, self.unchanged_count);
+        }
+        if self.deduped_count > 0 || self.conflict_count > 0 {
+            println!("重复的class文件: 内容相同 {}，内容不同 {}", self.deduped_count, self.conflict_count);
+        }
+        
+        self.print_jdk_versions();
+        
+        if !self.failures.is_empty() {
+            println!("\n-- 复制失败的文件（{} 个） --", self.failures.len());
+            for failure in &self.failures {
+                println!("{}: {}", failure.path, failure.error);
+            }
+        }
+        
+        if let Some(orphans) = &self.orphan_classes {
+            println!("\n-- 孤立class文件 --");
+            if orphans.is_empty() {
+                println!("无");
+            }
+            for orphan in orphans {
+                println!("{}", orphan.to_string_lossy());
+            }
+        }
+        
+        if let Some(compilers) = &self.compilers {
+            println!("\n-- 推测的编译器（根据class文件版本和SourceFile属性，仅供参考） --");
+            if compilers.is_empty() {
+                println!("无");
+            }
+            for (compiler, count) in compilers {
+                println!("{}: {} 个class文件", compiler, count);
+            }
+        }
+
+    }
+}
+
+/// Java类文件版本信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaClassVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl JavaClassVersion {
+    /// 返回指定JDK版本号对应的class文件主版本号（如 8 -> 52），超出主版本号的范围时返回None
+    pub fn major_for_jdk(jdk: u16) -> Option<u16> {
+        jdk.checked_add(44)
+    }
+
+    /// 是否使用了预览特性编译（`--enable-preview`会将次版本号设为0xFFFF）
+    pub fn is_preview(&self) -> bool {
+        self.minor == 0xFFFF
+    }
+
+    /// 返回人类可读的JDK版本字符串
+    pub fn to_jdk_version(&self) -> String {
+        match self.major {
+            45 => "JDK 1.1".to_string(),
+            46 => "JDK 1.2".to_string(),
+            47 => "JDK 1.3".to_string(),
+            48 => "JDK 1.4".to_string(),
+            // JDK 5起版本号与主版本号线性对应
+            major if major >= 49 => format!("JDK {}", major - 44),
+            _ => format!("未知JDK版本 (major: {})", self.major),
+        }
+    }
+}
+
+/// 找到的class文件位置
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ClassLocation {
+    /// class文件所在的class目录
+    pub class_dir: PathBuf,
+    /// 相对于class目录的路径
+    pub rel_path: PathBuf,
+    /// 从多版本目录`META-INF/versions/N`中选择时为N，此时`class_dir`为该版本目录
+    pub release: Option<u16>,
+}
+
+impl ClassLocation {
+    /// class文件的完整路径
+    pub fn path(&self) -> PathBuf {
+        self.class_dir.join(&self.rel_path)
+    }
+    
+    /// 文件所属的class目录，多版本变体返回`META-INF/versions/N`所在的class目录
+    fn root_class_dir(&self) -> &Path {
+        match self.release {
+            Some(_) => self.class_dir.ancestors().nth(3).unwrap_or(&self.class_dir),
+            None => &self.class_dir,
+        }
+    }
+}
+
+/// JSON报告中的class文件版本
+#[derive(Debug, Serialize)]
+pub struct VersionReport {
+    pub major: u16,
+    pub minor: u16,
+    pub jdk_version: String,
+    pub preview: bool,
+}
+
+/// JSON报告中的单个class文件
+#[derive(Debug, Serialize)]
+pub struct ClassReport {
+    pub path: String,
+    pub size: u64,
+    pub version: Option<VersionReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// 从多版本目录`META-INF/versions/N`中选择的版本N
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release: Option<u16>,
+    /// 使用--guess-compiler时推测的编译器，如`javac 17（预览）`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+}
+
+/// JSON报告中的单个源文件及其class文件
+#[derive(Debug, Serialize)]
+pub struct SourceReport {
+    pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    pub classes: Vec<ClassReport>,
+}
+
+/// JSON报告中的非Java文件
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// 复制失败的文件
+#[derive(Debug, Serialize)]
+pub struct FileFailure {
+    pub path: String,
+    pub error: String,
+}
+
+impl FileFailure {
+    /// 记录复制失败的文件，同时输出错误日志
+    fn new(rel_path: &Path, err: &anyhow::Error) -> FileFailure {
+        error!("复制失败，继续处理其他文件: {}: {:#}", rel_path.to_string_lossy(), err);
+        FileFailure {
+            path: rel_path.to_string_lossy().into_owned(),
+            error: format!("{:#}", err),
+        }
+    }
+}
+
+/// 输出到标准输出的JSON汇总信息
+#[derive(Debug, Serialize)]
+struct SummaryJson<'a> {
+    source_count: usize,
+    class_count: usize,
+    non_java_count: usize,
+    total_copied: usize,
+    jdk_versions: BTreeMap<&'a str, usize>,
+}
+
+/// 复制操作的完整JSON报告
+#[derive(Debug, Serialize)]
+struct CopyReport<'a> {
+    sources: &'a [SourceReport],
+    non_java_files: &'a [FileReport],
+    class_resources: &'a [FileReport],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    copied_sources: &'a [FileReport],
+    source_count: usize,
+    class_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    classes_per_source: Option<f64>,
+    non_java_count: usize,
+    class_resource_count: usize,
+    copied_source_count: usize,
+    total_copied: usize,
+    total_bytes: u64,
+    unchanged_count: usize,
+    version_skipped_count: usize,
+    since_skipped_count: usize,
+    synthetic_skipped_count: usize,
+    inner_skipped_count: usize,
+    invalid_skipped_count: usize,
+    missing_source_count: usize,
+    unchanged_source_count: usize,
+    deduped_count: usize,
+    conflict_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class_sha256: Option<String>,
+    jdk_versions: BTreeMap<&'a str, usize>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    failures: &'a [FileFailure],
+}
+
+/// 将源文件对应的class文件及非Java文件复制到输出目录
+///
+/// `class_dirs`按顺序查找，同一个class文件存在于多个目录且内容相同时只复制一次，
+/// 内容不同时按[`Options::duplicate_policy`]选择或报错。
+pub fn run(source_dir: &Path, class_dirs: &[PathBuf], output_dir: &Path, options: &Options) -> Result<Summary> {
+    run_with_sources(source_dir, class_dirs, output_dir, options, None)
+}
+
+/// 与[`run`]相同，`sources`不为None时只处理其中的源文件，代替遍历源代码目录
+#[instrument(level = "debug", skip_all, fields(source_dir = %source_dir.display()))]
+pub(crate) fn run_with_sources(source_dir: &Path, class_dirs: &[PathBuf], output_dir: &Path, options: &Options, sources: Option<&[PathBuf]>) -> Result<Summary> {
+    let started = Instant::now();
+    
+    // 检查路径是否存在
+    if !source_dir.exists() {
+        bail!("源代码路径不存在: {:?}", source_dir);
+    }
+    
+    if class_dirs.is_empty() && options.classpath_jars.is_empty() {
+        bail!("至少需要指定一个Class路径或class路径jar");
+    }
+    
+    for class_dir in class_dirs {
+        if !class_dir.exists() {
+            bail!("Class路径不存在: {:?}", class_dir);
+        }
+        if options.multi_release.is_none() && class_dir.join("META-INF").join("versions").is_dir() {
+            info!("Class路径 {:?} 包含多版本目录META-INF/versions，可使用--multi-release选择版本变体", class_dir);
+        }
+    }
+    
+    for jar_path in &options.classpath_jars {
+        if !jar_path.is_file() {
+            bail!("class路径jar不存在: {:?}", jar_path);
+        }
+    }
+    
+    if options.jar_per_package == Some(0) {
+        bail!("--jar-package-depth必须大于0");
+    }
+    
+    if options.symlink && options.hardlink {
+        bail!("--symlink不能与--hardlink同时使用");
+    }
+    if options.symlink && (options.strip_debug || options.normalize_eol.is_some()) {
+        bail!("--symlink不能与--strip-debug或--normalize-eol同时使用，改写后的内容无法链接到原文件");
+    }
+    
+    if options.buffer_size == Some(0) {
+        bail!("--buffer-size必须大于0");
+    }
+    
+    if options.class_max_depth == Some(0) {
+        bail!("--class-max-depth必须大于0，否则找不到任何class文件");
+    }
+    
+    // 只查找、审计或校验时不写入输出目录
+    let read_only = options.list_only || options.audit || options.verify_only;
+    
+    // 输出目录与源代码目录或class目录互相嵌套时，重复运行会把上次的输出当作输入
+    if !read_only {
+        check_not_nested(output_dir, source_dir, "源代码路径")?;
+        for class_dir in class_dirs {
+            check_not_nested(output_dir, class_dir, "Class路径")?;
+        }
+    }
+    
+    // 指定了--class-relative-to时，各class目录在输出目录中的前缀
+    let class_prefixes = match &options.class_relative_to {
+        Some(base) => class_output_prefixes(class_dirs, base, options)?,
+        None => HashMap::new(),
+    };
+    
+    // 演练模式下的输出前缀
+    let prefix = options.output_prefix();
+    
+    // 只需要jar文件时，先复制到临时目录再打包
+    if options.no_output_dir && options.jar.is_none() && options.update_jar.is_none() {
+        bail!("--no-output-dir需要与--jar或--update-jar同时使用");
+    }
+    if let Some(jar_path) = options.update_jar.as_ref().filter(|path| !path.is_file()) {
+        bail!("要更新的jar文件不存在: {:?}", jar_path);
+    }
+    let staging_dir = if (options.jar.is_some() || options.update_jar.is_some()) && options.no_output_dir && !options.dry_run && !read_only {
+        Some(tempfile::tempdir().context("创建临时目录失败")?)
+    } else {
+        None
+    };
+    
+    // 已有的输出目录必须为空，避免新旧文件混在一起
+    if staging_dir.is_none() && !read_only {
+        if options.atomic && options.clean {
+            bail!("--atomic会整体替换输出目录，不能与--clean同时使用");
+        }
+        check_output_dir_empty(output_dir, options)?;
+    }
+    
+    // 原子输出时先复制到输出目录旁的临时目录，复制完成后再替换输出目录，其他进程不会看到复制了一半的结果
+    let atomic_dir = if options.atomic && staging_dir.is_none() && !options.dry_run && !read_only {
+        Some(atomic_staging_dir(output_dir)?)
+    } else {
+        None
+    };
+    let final_output_dir = output_dir;
+    let output_dir = staging_dir.as_ref()
+        .or(atomic_dir.as_ref())
+        .map_or(output_dir, |dir| dir.path());
+    
+    // 创建输出目录（如果不存在）
+    if !options.dry_run && !read_only && !output_dir.exists() {
+        fs::create_dir_all(output_dir)?;
+    }
+    
+    // 收集所有源文件（包括Java和非Java文件），并按include/exclude规则过滤
+    let phase_started = Instant::now();
+    let phase_span = debug_span!("collect").entered();
+    let lookup = options.lookup_options();
+    let mapping = options.mapping.as_deref()
+        .map(|path| read_mapping(path, class_dirs))
+        .transpose()?;
+    let (java_files, non_java_files) = match (sources, &options.source_list) {
+        _ if mapping.is_some() => (Vec::new(), Vec::new()),
+        (Some(sources), _) => sources.iter()
+            .cloned()
+            .partition(|path| lookup.is_source_file(path)),
+        (None, Some(list)) => read_source_list(source_dir, list, &lookup)?,
+        (None, None) => collect_source_files(source_dir, &lookup)?,
+    };
+    let filter = PathFilter::new(&options.includes, &options.excludes)?;
+    let package_mapper = PackageMapper::new(&options.map_packages)?;
+    let class_renamer = ClassRenamer::new(&options.renames)?;
+    let java_files = filter.apply(source_dir, java_files);
+    let non_java_files = if options.no_resources || !options.classes.is_empty() {
+        Vec::new()
+    } else {
+        filter.apply(source_dir, non_java_files)
+    };
+    info!("找到 {} 个Java源文件，{} 个非Java文件", java_files.len(), non_java_files.len());
+    let collect_time = phase_started.elapsed();
+    drop(phase_span);
+    let phase_started = Instant::now();
+    let phase_span = debug_span!("resolve").entered();
+    
+    // 打开class路径jar，找到的class文件提取到临时目录
+    let jar_extract_dir = if options.classpath_jars.is_empty() {
+        None
+    } else {
+        Some(tempfile::tempdir().context("创建临时目录失败")?)
+    };
+    let classpath_jars = match &jar_extract_dir {
+        Some(extract_dir) => options.classpath_jars.iter()
+            .enumerate()
+            .map(|(index, jar_path)| jar::ClassPathJar::open(jar_path, extract_dir.path().join(index.to_string())))
+            .collect::<Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    
+    // 为每个源文件找到对应的class文件，记录所有找不到class文件的源文件
+    let mut missing: Vec<PathBuf> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut duplicates = Duplicates::default();
+    let mut source_packages: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut found_classes: HashSet<&str> = HashSet::new();
+    let mut inner_skipped_count = 0;
+    
+    // 记录源文件和对应的class文件
+    let mut source_to_classes: BTreeMap<PathBuf, Vec<ClassLocation>> = BTreeMap::new();
+    
+    for java_file in &java_files {
+        let java_rel_path = java_file.strip_prefix(source_dir)
+            .with_context(|| format!("无法获取相对路径: {:?}", java_file))?;
+        
+        let mut type_names = source_type_names(java_file)?;
+        
+        // 按声明的包名查找class文件，没有声明时仍使用源文件所在目录
+        let lookup_rel_path = match options.derive_package_from_source.then(|| read_package(java_file)).transpose()?.flatten() {
+            Some(package) => {
+                let package_dir: PathBuf = package.split('.').collect();
+                let lookup_rel_path = package_dir.join(java_rel_path.file_name().unwrap_or_default());
+                source_packages.insert(java_rel_path.to_path_buf(), package_dir);
+                lookup_rel_path
+            }
+            None => java_rel_path.to_path_buf(),
+        };
+        
+        // 指定了--class时只查找源文件中被选中的类，没有选中任何类的源文件直接跳过
+        let selected: Vec<&str> = if options.classes.is_empty() {
+            Vec::new()
+        } else {
+            let package = package_name(lookup_rel_path.parent().unwrap_or(Path::new("")));
+            let selected: Vec<&str> = options.classes.iter()
+                .map(String::as_str)
+                .filter(|class_name| {
+                    let (class_package, simple_name) = class_name.rsplit_once('.').unwrap_or(("", class_name));
+                    let outer_name = simple_name.split('$').next().unwrap_or(simple_name);
+                    class_package == package && type_names.iter().any(|type_name| type_name == OsStr::new(outer_name))
+                })
+                .collect();
+            if selected.is_empty() {
+                continue;
+            }
+            type_names = selected.iter()
+                .map(|class_name| OsString::from(class_name.rsplit_once('.').map_or(*class_name, |(_, simple_name)| simple_name)))
+                .collect();
+            selected
+        };
+        
+        let mut class_files = resolve_class_files(class_dirs, &lookup_rel_path, &type_names, &lookup, &mut duplicates, &mut warnings)?;
+        resolve_jar_class_files(&classpath_jars, &lookup_rel_path, &type_names, &lookup, &mut class_files, &mut duplicates, &mut warnings)?;
+        
+        // 只保留文件名与类型名完全相同的class文件
+        if options.no_inner_classes {
+            let found_count = class_files.len();
+            class_files.retain(|location| location.rel_path.file_stem()
+                .is_some_and(|stem| is_type_name(stem, &type_names, lookup.ignore_case)));
+            inner_skipped_count += found_count - class_files.len();
+        }
+        
+        for (class_name, type_name) in selected.iter().zip(&type_names) {
+            let found = class_files.iter().any(|location| location.rel_path.file_stem()
+                .is_some_and(|stem| matches_type_names(stem, std::slice::from_ref(type_name), lookup.ignore_case)));
+            if found {
+                found_classes.insert(class_name);
+            }
+        }
+        
+        if class_files.is_empty() {
+            missing.push(java_rel_path.to_path_buf());
+            continue;
+        }
+        
+        class_files.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        if lookup.ignore_case {
+            warn_case_mismatch(java_rel_path, &class_files, &type_names, &mut warnings);
+        }
+        warn_divergent_packages(java_rel_path, &lookup_rel_path, &class_files, &mut warnings);
+        source_to_classes.insert(java_rel_path.to_path_buf(), class_files);
+    }
+    
+    // 使用外部提供的对应关系
+    if let Some(mapping) = mapping {
+        info!("从映射文件读取 {} 个源文件的对应关系", mapping.len());
+        for (java_rel_path, class_files) in mapping {
+            if class_files.is_empty() {
+                missing.push(java_rel_path);
+            } else {
+                source_to_classes.insert(java_rel_path, class_files);
+            }
+        }
+    }
+    
+    // 每个指定的类都必须找到class文件
+    let unresolved: Vec<&String> = options.classes.iter()
+        .filter(|class_name| !found_classes.contains(class_name.as_str()))
+        .collect();
+    if !unresolved.is_empty() {
+        for class_name in &unresolved {
+            error!("找不到指定的类对应的class文件: {}", class_name);
+        }
+        bail!("{} 个指定的类找不到对应的class文件", unresolved.len());
+    }
+    
+    // 审计和校验模式只读取class文件版本，不复制文件
+    if options.audit || options.verify_only {
+        if options.verify_only && !missing.is_empty() {
+            for java_rel_path in &missing {
+                error!("找不到Java文件对应的class文件: {:?}", java_rel_path);
+            }
+            return Err(Error::MissingClasses { count: missing.len() }.into());
+        }
+        let jdk_versions = audit_class_versions(&source_to_classes, options, &mut warnings)?;
+        if options.verify_only {
+            if let Some(target_jdk) = options.target_jdk {
+                check_target_jdk(&jdk_versions, target_jdk)?;
+            }
+        }
+        warn_mixed_versions(&jdk_versions, &mut warnings);
+        return Ok(Summary {
+            source_count: source_to_classes.len(),
+            found_class_count: source_to_classes.values().map(Vec::len).sum(),
+            missing_source_count: missing.len(),
+            inner_skipped_count,
+            jdk_versions,
+            warnings,
+            timings: Timings {
+                collect: collect_time,
+                resolve: phase_started.elapsed(),
+                total: started.elapsed(),
+                ..Timings::default()
+            },
+            ..Summary::default()
+        });
+    }
+    
+    // 只列出对应关系时到此为止，不读取class文件版本
+    if options.list_only {
+        print_resolutions(&source_to_classes, &missing);
+        return Ok(Summary {
+            source_count: source_to_classes.len(),
+            found_class_count: source_to_classes.values().map(Vec::len).sum(),
+            missing_source_count: missing.len(),
+            inner_skipped_count,
+            warnings,
+            timings: Timings { total: started.elapsed(), ..Timings::default() },
+            ..Summary::default()
+        });
+    }
+    
+    warn_duplicate_simple_names(&source_to_classes, &mut warnings);
+    
+    // 允许缺少class文件时只发出警告，跳过这些源文件
+    if options.allow_missing {
+        for java_rel_path in &missing {
+            warnings.push(Warning::new(WarningKind::MissingClasses, Some(java_rel_path),
+                format!("找不到Java文件对应的class文件，已跳过: {}", java_rel_path.to_string_lossy())));
+        }
+    }
+    
+    // 如果有任何错误，不复制文件
+    if !missing.is_empty() && !options.allow_missing {
+        for java_rel_path in &missing {
+            error!("找不到Java文件对应的class文件: {:?}", java_rel_path);
+        }
+        return Err(Error::MissingClasses { count: missing.len() }.into());
+    }
+    
+    // 内容不同的同名class文件需要明确选择使用哪一个
+    if options.duplicate_policy == DuplicatePolicy::Error && !duplicates.conflicts.is_empty() {
+        for rel_path in &duplicates.conflicts {
+            error!("class文件存在于多个位置且内容不同: {:?}", rel_path);
+        }
+        return Err(Error::ClassConflicts { count: duplicates.conflicts.len() }.into());
+    }
+    
+    // 平铺输出时不同包中的同名class文件会互相覆盖
+    if options.output_layout == OutputLayout::Flat {
+        check_flat_collisions(&source_to_classes, &class_renamer, options.force, &mut warnings)?;
+    } else if !class_renamer.is_empty() {
+        warn_rename_collisions(&source_to_classes, &class_renamer, &mut warnings);
+    }
+    
+    let mut summary = Summary {
+        source_count: source_to_classes.len(),
+        found_class_count: source_to_classes.values().map(Vec::len).sum(),
+        missing_source_count: missing.len(),
+        inner_skipped_count,
+        deduped_count: duplicates.deduped,
+        conflict_count: duplicates.conflicts.len(),
+        ..Summary::default()
+    };
+    
+    // class文件明显少于源文件时可能只编译了部分源文件
+    if let (Some(min), Some(ratio)) = (options.min_classes_per_source, summary.classes_per_source()) {
+        if ratio < min {
+            warnings.push(Warning::new(WarningKind::LowClassRatio, None,
+                format!("平均每个源文件只有 {:.2} 个class文件，低于 {}，可能只编译了部分源文件", ratio, min)));
+        }
+    }
+    
+    // 已复制的包中还有其他主类的内部类时，主类可能被过滤掉了
+    warn_missing_dependencies(class_dirs, &source_to_classes, &mut warnings);
+    
+    // 查找没有对应源文件的class文件
+    if options.report_orphans {
+        summary.orphan_classes = Some(find_orphan_classes(class_dirs, &source_to_classes, &lookup)?);
+    }
+    
+    // 查找class目录中的资源文件，源代码目录中已有的同名文件优先
+    let class_resources = if options.include_class_resources && options.classes.is_empty() {
+        let non_java_rel_paths: HashSet<&Path> = non_java_files.iter()
+            .filter_map(|path| path.strip_prefix(source_dir).ok())
+            .collect();
+        collect_class_resources(class_dirs, &non_java_rel_paths, &lookup, &mut warnings)?
+    } else {
+        Vec::new()
+    };
+    
+    // 在不区分大小写的文件系统上，只有大小写不同的目标路径会互相覆盖
+    if options.case_check {
+        let non_java_targets = non_java_files.iter()
+            .filter_map(|path| path.strip_prefix(source_dir).ok())
+            .map(|rel_path| package_mapper.map(rel_path));
+        let class_targets = source_to_classes.values()
+            .flatten()
+            .map(|location| class_target_rel_path(location, options, &class_prefixes, &package_mapper, &class_renamer));
+        let resource_targets = class_resources.iter()
+            .map(|location| package_mapper.map(&class_prefixes.get(location.root_class_dir())
+                .map_or_else(|| location.rel_path.clone(), |prefix| prefix.join(&location.rel_path))));
+        let source_targets = source_to_classes.keys()
+            .filter(|_| options.copy_sources)
+            .map(|java_rel_path| package_mapper.map(java_rel_path));
+        check_case_collisions(non_java_targets.chain(class_targets).chain(resource_targets).chain(source_targets))?;
+    }
+    
+    // 复制前检查是否有class文件超出目标JDK版本，失败时输出目录中不会留下这些文件
+    if let Some(target_jdk) = options.target_jdk {
+        let jdk_versions = versions_to_copy(&source_to_classes, options)?;
+        check_target_jdk(&jdk_versions, target_jdk)?;
+    }
+    summary.timings.collect = collect_time;
+    summary.timings.resolve = phase_started.elapsed();
+    drop(phase_span);
+    let phase_started = Instant::now();
+    let phase_span = debug_span!("copy").entered();
+    
+    // 输出到终端时显示进度条，并隐藏每个文件的详细信息
+    let progress = if !options.verbose && !options.quiet && std::io::stdout().is_terminal() {
+        let copied_sources = if options.copy_sources { source_to_classes.len() } else { 0 };
+        let total = non_java_files.len() + class_resources.len() + copied_sources + source_to_classes.values().map(Vec::len).sum::<usize>();
+        Some(progress::CopyProgress::new(total as u64, prefix)?)
+    } else {
+        None
+    };
+    // 是否逐行打印每个文件的详细信息
+    let details = !options.quiet && progress.is_none();
+    
+    // 读取class文件版本缓存
+    let version_cache = options.version_cache.as_deref()
+        .map(cache::VersionCache::load)
+        .transpose()?;
+    
+    // 逐个文件写入的JSON Lines报告
+    let report_stream = options.report_jsonl.as_deref()
+        .map(jsonl::JsonlReport::create)
+        .transpose()?;
+    
+    // 读取版本和复制文件使用各自的线程池，分别限制并发数
+    let read_pool = options.read_pool()?;
+    let copy_pool = options.copy_pool()?;
+    
+    let context = CopyContext {
+        source_dir,
+        output_dir,
+        options,
+        progress: progress.as_ref(),
+        details,
+        version_cache: version_cache.as_ref(),
+        read_pool: &read_pool,
+        package_mapper: &package_mapper,
+        class_renamer: &class_renamer,
+        report_stream: report_stream.as_ref(),
+        class_prefixes: &class_prefixes,
+        source_packages: &source_packages,
+        color: color_enabled(&std::io::stdout()),
+        hardlink_fallbacks: AtomicUsize::new(0),
+    };
+    
+    // 首先复制非Java文件
+    if !options.quiet && !options.no_resources {
+        print_line(progress.as_ref(), "开始复制非Java文件...");
+    }
+    
+    for non_java_file in &non_java_files {
+        let rel_path = non_java_file.strip_prefix(source_dir)
+            .with_context(|| format!("无法获取相对路径: {:?}", non_java_file))?;
+        
+        let (report, unchanged) = match copy_plain_file(&context, non_java_file, rel_path, "非Java文件") {
+            Ok(copied) => copied,
+            Err(err) if options.keep_going => {
+                summary.failures.push(FileFailure::new(rel_path, &err));
+                if let Some(bar) = &progress {
+                    bar.inc(1);
+                }
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(stream) = &report_stream {
+            stream.write_file("non_java", &report)?;
+        }
+        if unchanged {
+            summary.unchanged_count += 1;
+        }
+        summary.non_java_files.push(report);
+        summary.non_java_count += 1;
+    }
+    
+    if summary.non_java_count > 0 && details {
+        println!("----------------------------------------");
+    }
+    
+    // 复制所有class文件到输出目录并检查版本
+    if !options.quiet {
+        print_line(progress.as_ref(), "开始复制Java文件对应的class文件并检查JDK版本...");
+    }
+    
+    // 按源文件并行复制class文件，各线程的结果最后统一合并
+    let results = copy_pool.install(|| {
+        source_to_classes.par_iter()
+            .map(|(java_rel_path, class_files)| copy_class_files(&context, java_rel_path, class_files))
+            .collect::<Result<Vec<_>>>()
+    })?;
+    
+    for result in results {
+        if details {
+            print!("{}", result.output);
+        }
+        for (version, class_file) in result.versions {
+            summary.jdk_versions.entry(version)
+                .or_default()
+                .push(class_file);
+        }
+        summary.class_count += result.report.classes.len();
+        summary.preview_count += result.report.classes.iter()
+            .filter(|class| class.version.as_ref().is_some_and(|v| v.preview))
+            .count();
+        summary.unchanged_count += result.unchanged;
+        summary.version_skipped_count += result.version_skipped;
+        summary.since_skipped_count += result.since_skipped;
+        summary.synthetic_skipped_count += result.synthetic_skipped;
+        summary.invalid_skipped_count += result.invalid_skipped;
+        if result.since_skipped > 0 && result.report.classes.is_empty() && result.version_skipped == 0 {
+            summary.unchanged_source_count += 1;
+        }
+        warnings.extend(result.warnings);
+        summary.failures.extend(result.failures);
+        summary.sources.push(result.report);
+    }
+    if details {
+        println!("----------------------------------------");
+    }
+    
+    // 复制class目录中的资源文件
+    if !class_resources.is_empty() && !options.quiet {
+        print_line(progress.as_ref(), "开始复制class目录中的资源文件...");
+    }
+    for location in &class_resources {
+        let rel_path = context.with_class_prefix(location, location.rel_path.clone());
+        let (report, unchanged) = match copy_plain_file(&context, &location.path(), &rel_path, "资源文件") {
+            Ok(copied) => copied,
+            Err(err) if options.keep_going => {
+                summary.failures.push(FileFailure::new(&location.rel_path, &err));
+                if let Some(bar) = &progress {
+                    bar.inc(1);
+                }
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(stream) = &report_stream {
+            stream.write_file("resource", &report)?;
+        }
+        if unchanged {
+            summary.unchanged_count += 1;
+        }
+        summary.class_resources.push(report);
+        summary.class_resource_count += 1;
+    }
+    if !class_resources.is_empty() && details {
+        println!("----------------------------------------");
+    }
+    
+    // 复制源文件本身，保持源代码目录中的相对路径
+    if options.copy_sources && !source_to_classes.is_empty() {
+        if !options.quiet {
+            print_line(progress.as_ref(), "开始复制源文件...");
+        }
+        for java_rel_path in source_to_classes.keys() {
+            let (report, unchanged) = match copy_plain_file(&context, &source_dir.join(java_rel_path), java_rel_path, "源文件") {
+                Ok(copied) => copied,
+                Err(err) if options.keep_going => {
+                    summary.failures.push(FileFailure::new(java_rel_path, &err));
+                    if let Some(bar) = &progress {
+                        bar.inc(1);
+                    }
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            if let Some(stream) = &report_stream {
+                stream.write_file("source", &report)?;
+            }
+            if unchanged {
+                summary.unchanged_count += 1;
+            }
+            summary.copied_sources.push(report);
+            summary.copied_source_count += 1;
+        }
+        if details {
+            println!("----------------------------------------");
+        }
+    }
+    
+    if let Some(bar) = &progress {
+        bar.finish_and_clear();
+    }
+    summary.timings.copy = phase_started.elapsed();
+    drop(phase_span);
+    
+    if options.symlink && !options.dry_run {
+        warnings.push(Warning::new(WarningKind::SymlinkOutput, None,
+            format!("输出目录中的文件是指向class目录和源代码目录的符号链接，不能脱离原目录单独复制或部署: {:?}", output_dir)));
+    }
+    if options.guess_compiler {
+        let mut compilers: BTreeMap<String, usize> = BTreeMap::new();
+        for compiler in summary.sources.iter().flat_map(|source| &source.classes).filter_map(|class| class.compiler.clone()) {
+            *compilers.entry(compiler).or_default() += 1;
+        }
+        summary.compilers = Some(compilers);
+    }
+    let hardlink_fallbacks = context.hardlink_fallbacks.load(Ordering::Relaxed);
+    if hardlink_fallbacks > 0 {
+        warnings.push(Warning::new(WarningKind::HardlinkFallback, None,
+            format!("{} 个文件无法创建硬链接（如源文件与输出目录不在同一文件系统上），已改为复制", hardlink_fallbacks)));
+    }
+    package_mapper.warn_unmatched(&mut warnings);
+    warn_mixed_versions(&summary.jdk_versions, &mut warnings);
+    summary.warnings = warnings;
+    
+    if let Some(warnings_path) = &options.warnings_file {
+        warning::write_warnings(warnings_path, &summary.warnings)?;
+        info!("警告已写入 {:?}", warnings_path);
+    }
+    
+    if let Some(cache) = &version_cache {
+        info!("版本缓存命中 {} 个class文件", cache.hits());
+        cache.save()?;
+    }
+    
+    // 要求所有class文件的JDK版本一致
+    if options.fail_on_mixed_versions && summary.jdk_versions.len() > 1 {
+        let counts: Vec<String> = summary.jdk_versions.iter()
+            .map(|(version, files)| format!("  {}: {} 个文件", version, files.len()))
+            .collect();
+        bail!("检测到 {} 个不同的JDK版本:\n{}", summary.jdk_versions.len(), counts.join("\n"));
+    }
+    
+    // 所有文件复制完成后替换输出目录
+    let output_dir = match atomic_dir {
+        Some(dir) => {
+            replace_output_dir(dir, final_output_dir)?;
+            info!("已用临时目录替换输出目录 {:?}", final_output_dir);
+            final_output_dir
+        }
+        None => output_dir,
+    };
+    
+    // 打印输出目录的树形结构
+    if options.tree {
+        if options.dry_run {
+            info!("演练模式下没有复制文件，不打印输出目录的树形结构");
+        } else {
+            print!("\n{}", render_tree(output_dir, options)?);
+        }
+    }
+    
+    // 写入JSON报告
+    if let Some(report_path) = &options.report {
+        write_report(report_path, &summary)?;
+        info!("JSON报告已写入 {:?}", report_path);
+    }
+    
+    // 写入文本清单
+    if let Some(manifest_path) = &options.manifest {
+        write_manifest(manifest_path, &source_to_classes, &summary.non_java_files, &summary.class_resources)?;
+        info!("清单已写入 {:?}", manifest_path);
+    }
+    
+    // 写入文件清册
+    if let Some(inventory_path) = &options.inventory {
+        write_inventory(inventory_path, &summary)?;
+        info!("文件清册已写入 {:?}", inventory_path);
+    }
+    
+    // 打包为jar文件
+    if let Some(jar_path) = options.jar.as_ref().filter(|_| !options.dry_run) {
+        let entries = jar::write_jar_from_dir(jar_path, output_dir)?;
+        if options.quiet {
+            info!("已生成jar文件: {}，共 {} 个条目", jar_path.display(), entries);
+        } else {
+            println!("已生成jar文件: {}，共 {} 个条目", jar_path.display(), entries);
+        }
+    }
+    
+    // 更新已有的jar文件
+    if let Some(jar_path) = options.update_jar.as_ref().filter(|_| !options.dry_run) {
+        let update = jar::update_jar_from_dir(jar_path, output_dir)?;
+        let message = format!("已更新jar文件: {}，新增 {} 个条目，替换 {} 个条目，{} 个条目未变化",
+            jar_path.display(), update.added, update.replaced, update.unchanged);
+        if options.quiet {
+            info!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+    
+    // 按包分别打包为jar文件
+    if let Some(depth) = options.jar_per_package.filter(|_| !options.dry_run) {
+        let jars = jar::write_package_jars(output_dir, depth)?;
+        for (jar_path, entries) in &jars {
+            if options.quiet {
+                info!("已生成jar文件: {}，共 {} 个条目", jar_path.display(), entries);
+            } else {
+                println!("已生成jar文件: {}，共 {} 个条目", jar_path.display(), entries);
+            }
+        }
+        info!("按包生成 {} 个jar文件", jars.len());
+    }
+    
+    // 与期望的文件列表比较
+    if let Some(expect_path) = &options.expect {
+        if options.dry_run {
+            info!("演练模式下没有复制文件，不检查期望的文件列表");
+        } else {
+            check_expected_files(output_dir, expect_path)?;
+        }
+    }
+    
+    if options.dry_run {
+        info!("演练模式: 将复制 {} 个class文件和 {} 个非Java文件到 {:?}", summary.class_count, summary.non_java_count, output_dir);
+    } else {
+        info!("成功复制 {} 个class文件和 {} 个非Java文件到 {:?}", summary.class_count, summary.non_java_count, output_dir);
+    }
+    summary.timings.total = started.elapsed();
+    Ok(summary)
+}
+
+/// 比较输出目录中的文件与期望的文件列表（每行一个以`/`分隔的相对路径，忽略空行），列出缺少和多出的文件
+fn check_expected_files(output_dir: &Path, expect_path: &Path) -> Result<()> {
+    let content = fs::read_to_string(expect_path)
+        .with_context(|| format!("无法读取期望的文件列表: {:?}", expect_path))?;
+    let expected: BTreeSet<&str> = content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    
+    let mut actual = BTreeSet::new();
+    for entry in WalkDir::new(output_dir) {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel_path = entry.path().strip_prefix(output_dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+            actual.insert(jar::entry_name(rel_path));
+        }
+    }
+    
+    let missing: Vec<_> = expected.iter().filter(|path| !actual.contains(**path)).collect();
+    let unexpected: Vec<_> = actual.iter().filter(|path| !expected.contains(path.as_str())).collect();
+    for path in &missing {
+        error!("输出目录中缺少期望的文件: {}", path);
+    }
+    for path in &unexpected {
+        error!("输出目录中有未期望的文件: {}", path);
+    }
+    if !missing.is_empty() || !unexpected.is_empty() {
+        bail!("输出目录与期望的文件列表不一致: 缺少 {} 个文件，多出 {} 个文件", missing.len(), unexpected.len());
+    }
+    info!("输出目录与期望的文件列表一致，共 {} 个文件", actual.len());
+    Ok(())
+}
+
+/// 以类似`tree`命令的格式渲染目录结构，文件标注大小，class文件另外标注JDK版本
+fn render_tree(root: &Path, options: &Options) -> Result<String> {
+    let mut output = format!("{}\n", root.display());
+    render_tree_entries(root, "", options, &mut output)?;
+    Ok(output)
+}
+
+/// 递归渲染目录下的条目，`indent`为当前层级每行的前缀
+fn render_tree_entries(dir: &Path, indent: &str, options: &Options, output: &mut String) -> Result<()> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("无法读取目录: {:?}", dir))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("无法读取目录: {:?}", dir))?;
+    entries.sort_by_key(|entry| entry.file_name());
+    
+    for (index, entry) in entries.iter().enumerate() {
+        let last = index + 1 == entries.len();
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        output.push_str(indent);
+        output.push_str(if last { "└── " } else { "├── " });
+        
+        if entry.file_type()?.is_dir() {
+            output.push_str(&name);
+            output.push('\n');
+            let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            render_tree_entries(&path, &child_indent, options, output)?;
+            continue;
+        }
+        
+        let size = entry.metadata()
+            .with_context(|| format!("无法获取文件元数据: {:?}", path))?.len();
+        let version = if path.extension().is_some_and(|ext| ext == "class") {
+            read_class_file_version(&path).ok().map(|version| version.to_jdk_version())
+        } else {
+            None
+        };
+        match version {
+            Some(version) => output.push_str(&format!("{} ({}, {})\n", name, options.display_size(size), version)),
+            None => output.push_str(&format!("{} ({})\n", name, options.display_size(size))),
+        }
+    }
+    Ok(())
+}
+
+/// 检查输出目录与输入目录是否互相嵌套
+fn check_not_nested(output_dir: &Path, input_dir: &Path, input_name: &str) -> Result<()> {
+    let output = canonicalize_lenient(output_dir)?;
+    let input = fs::canonicalize(input_dir)
+        .with_context(|| format!("无法解析路径: {:?}", input_dir))?;
+    
+    if output.starts_with(&input) || input.starts_with(&output) {
+        bail!("输出目录 {:?} 与{} {:?} 互相嵌套，请使用独立的输出目录", output_dir, input_name, input_dir);
+    }
+    Ok(())
+}
+
+/// 计算每个class目录相对于`base`的路径，作为其中的文件在输出目录中的前缀
+///
+/// 每个class目录都必须位于`base`下，且不能与class路径jar同时使用。
+fn class_output_prefixes(class_dirs: &[PathBuf], base: &Path, options: &Options) -> Result<HashMap<PathBuf, PathBuf>> {
+    if !options.classpath_jars.is_empty() {
+        bail!("--class-relative-to不能与class路径jar同时使用");
+    }
+    let canonical_base = fs::canonicalize(base)
+        .with_context(|| format!("--class-relative-to指定的目录不存在: {:?}", base))?;
+    
+    let mut prefixes = HashMap::new();
+    for class_dir in class_dirs {
+        let canonical_class_dir = fs::canonicalize(class_dir)
+            .with_context(|| format!("无法解析路径: {:?}", class_dir))?;
+        let Ok(prefix) = canonical_class_dir.strip_prefix(&canonical_base) else {
+            bail!("Class路径 {:?} 不在--class-relative-to指定的目录 {:?} 下", class_dir, base);
+        };
+        prefixes.insert(class_dir.clone(), prefix.to_path_buf());
+    }
+    Ok(prefixes)
+}
+
+/// 在输出目录旁（同一父目录下）创建`--atomic`使用的临时目录，使之后的重命名不跨文件系统
+fn atomic_staging_dir(output_dir: &Path) -> Result<tempfile::TempDir> {
+    let parent = output_dir.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(parent)
+        .with_context(|| format!("无法创建目录: {:?}", parent))?;
+    let name = output_dir.file_name().unwrap_or_default().to_string_lossy();
+    tempfile::Builder::new()
+        .prefix(&format!(".{}.tmp", name))
+        .tempdir_in(parent)
+        .with_context(|| format!("无法在 {:?} 中创建临时目录", parent))
+}
+
+/// 用临时目录替换输出目录
+///
+/// 已有的输出目录先重命名为备份，临时目录重命名为输出目录后再删除备份。输出目录是挂载点等无法重命名的情况下，
+/// 改为清空输出目录后复制临时目录中的文件，这时替换不再是原子的。
+fn replace_output_dir(staging: tempfile::TempDir, output_dir: &Path) -> Result<()> {
+    let staging_path = staging.keep();
+    match rename_output_dir(&staging_path, output_dir) {
+        Ok(()) => Ok(()),
+        Err(err) if matches!(err.kind(), std::io::ErrorKind::CrossesDevices | std::io::ErrorKind::ResourceBusy) => {
+            warn!("无法通过重命名替换输出目录 {:?}（{}），改为复制文件，替换过程不再是原子的", output_dir, err);
+            clear_dir(output_dir)?;
+            copy_dir_all(&staging_path, output_dir)?;
+            fs::remove_dir_all(&staging_path)
+                .with_context(|| format!("删除临时目录失败: {:?}", staging_path))
+        }
+        Err(err) => {
+            // 保留临时目录中已复制的文件，便于排查
+            Err(err).with_context(|| format!("替换输出目录失败: {:?} -> {:?}", staging_path, output_dir))
+        }
+    }
+}
+
+/// 将已有的输出目录重命名为备份，再将`staging`重命名为输出目录，失败时恢复备份
+fn rename_output_dir(staging: &Path, output_dir: &Path) -> std::io::Result<()> {
+    let backup = output_dir.with_file_name(format!(".{}.old-{}",
+        output_dir.file_name().unwrap_or_default().to_string_lossy(),
+        std::process::id()
+    ));
+    let has_existing = output_dir.exists();
+    if has_existing {
+        fs::rename(output_dir, &backup)?;
+    }
+    if let Err(err) = fs::rename(staging, output_dir) {
+        if has_existing {
+            fs::rename(&backup, output_dir)?;
+        }
+        return Err(err);
+    }
+    if has_existing {
+        if let Err(err) = fs::remove_dir_all(&backup) {
+            warn!("删除旧的输出目录失败: {:?}: {}", backup, err);
+        }
+    }
+    Ok(())
+}
+
+/// 删除目录中的所有文件和子目录，保留目录本身
+fn clear_dir(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir).with_context(|| format!("无法读取目录: {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        let result = if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        result.with_context(|| format!("删除失败: {:?}", path))?;
+    }
+    Ok(())
+}
+
+/// 递归复制目录中的所有文件，保留目录结构
+fn copy_dir_all(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let rel_path = entry.path().strip_prefix(from)
+            .with_context(|| format!("无法获取相对路径: {:?}", entry.path()))?;
+        let target = to.join(rel_path);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)
+                .with_context(|| format!("无法创建目录: {:?}", target))?;
+        } else {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("复制文件失败: {:?} -> {:?}", entry.path(), target))?;
+        }
+    }
+    Ok(())
+}
+
+/// 检查输出目录是否为空，非空时除非指定了`force`否则返回错误，同时指定`clean`时清空目录
+fn check_output_dir_empty(output_dir: &Path, options: &Options) -> Result<()> {
+    if options.clean && !options.force {
+        bail!("--clean需要与--force同时使用");
+    }
+    if !output_dir.is_dir() {
+        return Ok(());
+    }
+    
+    let entries = fs::read_dir(output_dir)
+        .with_context(|| format!("无法读取输出目录: {:?}", output_dir))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("无法读取输出目录: {:?}", output_dir))?;
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if !options.force {
+        bail!("输出目录不为空: {:?}，使用--force写入已有目录，或同时使用--clean先清空", output_dir);
+    }
+    if !options.clean {
+        return Ok(());
+    }
+    
+    if options.dry_run {
+        if !options.quiet {
+            println!("{}清空输出目录: {}，共 {} 项", options.output_prefix(), output_dir.display(), entries.len());
+        }
+        return Ok(());
+    }
+    for entry in entries {
+        let path = entry.path();
+        let result = if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        result.with_context(|| format!("清空输出目录失败: {:?}", path))?;
+    }
+    info!("已清空输出目录 {:?}", output_dir);
+    Ok(())
+}
+
+/// 规范化路径，路径不存在时规范化其最近的已存在上级目录后再拼接剩余部分
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf> {
+    let absolute = std::path::absolute(path)
+        .with_context(|| format!("无法解析路径: {:?}", path))?;
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            },
+            _ => break,
+        }
+    }
+    
+    let mut canonical = fs::canonicalize(existing)
+        .with_context(|| format!("无法解析路径: {:?}", existing))?;
+    canonical.extend(rest.iter().rev());
+    Ok(canonical)
+}
+
+/// 输出流是否使用颜色：输出到终端，且没有设置非空的`NO_COLOR`环境变量
+pub fn color_enabled(stream: &impl IsTerminal) -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()) && stream.is_terminal()
+}
+
+/// 为JDK版本着色：未超出`--target-jdk`时为绿色，超出时为红色，无法识别版本时为黄色
+fn colorize_version(text: &str, major: Option<u16>, target_jdk: Option<u16>) -> String {
+    match (major, target_jdk) {
+        (None, _) => text.yellow().to_string(),
+        (Some(major), Some(target_jdk)) if JavaClassVersion::major_for_jdk(target_jdk).is_some_and(|max_major| major > max_major) => text.red().to_string(),
+        (Some(_), Some(_)) => text.green().to_string(),
+        (Some(_), None) => text.to_string(),
+    }
+}
+
+/// 打印一行输出，进度条显示时打印在进度条上方
+fn print_line(progress: Option<&progress::CopyProgress>, line: &str) {
+    match progress {
+        Some(bar) => bar.println(line),
+        None => println!("{}", line),
+    }
+}
+
+/// 复制class文件时共享的上下文
+struct CopyContext<'a> {
+    source_dir: &'a Path,
+    output_dir: &'a Path,
+    options: &'a Options,
+    /// 进度条
+    progress: Option<&'a progress::CopyProgress>,
+    /// 是否打印每个文件的详细信息
+    details: bool,
+    /// class文件版本缓存
+    version_cache: Option<&'a cache::VersionCache>,
+    /// 读取class文件版本的线程池，复制线程读取版本时在其中执行
+    read_pool: &'a rayon::ThreadPool,
+    /// 包目录重定位规则
+    package_mapper: &'a PackageMapper,
+    /// class文件重命名规则
+    class_renamer: &'a ClassRenamer,
+    /// JSON Lines报告
+    report_stream: Option<&'a jsonl::JsonlReport>,
+    /// 各class目录相对于--class-relative-to的前缀
+    class_prefixes: &'a HashMap<PathBuf, PathBuf>,
+    /// 使用--derive-package-from-source时各源文件（相对路径）声明的包目录
+    source_packages: &'a HashMap<PathBuf, PathBuf>,
+    /// 输出是否使用颜色
+    color: bool,
+    /// 无法创建硬链接、改为复制的文件数
+    hardlink_fallbacks: AtomicUsize,
+}
+
+impl CopyContext<'_> {
+    /// 为class目录中的文件在输出目录中的相对路径加上所属class目录的前缀
+    fn with_class_prefix(&self, location: &ClassLocation, rel_path: PathBuf) -> PathBuf {
+        match self.class_prefixes.get(location.root_class_dir()) {
+            Some(prefix) => prefix.join(rel_path),
+            None => rel_path,
+        }
+    }
+    
+    /// 在读取线程池中读取class文件版本，使用版本缓存时先查找缓存
+    ///
+    /// 复制线程在此等待读取完成，同时读取版本的线程数不超过读取线程池的大小。
+    fn read_version(&self, class_file: &Path) -> Result<JavaClassVersion> {
+        self.read_pool.install(|| match self.version_cache {
+            Some(cache) => cache.read_version(class_file),
+            None => read_class_file_version(class_file),
+        })
+    }
+    
+    /// 源文件的包目录，优先使用源文件中声明的包名
+    fn package_dir<'p>(&'p self, java_rel_path: &'p Path) -> &'p Path {
+        self.source_packages.get(java_rel_path)
+            .map_or_else(|| java_rel_path.parent().unwrap_or(Path::new("")), PathBuf::as_path)
+    }
+}
+
+/// `--bucket-by-version`时存放非class文件的目录
+const RESOURCES_BUCKET: &str = "resources";
+
+/// `--bucket-by-version`时class文件所在的目录名，如`JDK 8`对应`jdk8`，无法识别版本时为`unknown`
+fn version_bucket(version: Option<&VersionReport>) -> String {
+    let bucket: String = version
+        .map(|version| version.jdk_version.chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '.')
+            .map(|c| c.to_ascii_lowercase())
+            .collect())
+        .unwrap_or_default();
+    if bucket.starts_with("jdk") && bucket.len() > 3 {
+        bucket
+    } else {
+        "unknown".to_string()
+    }
+}
+
+/// 复制一个非class文件（非Java文件或class目录中的资源文件），返回其报告以及目标文件是否未变化
+fn copy_plain_file(context: &CopyContext, source: &Path, rel_path: &Path, label: &str) -> Result<(FileReport, bool)> {
+    let options = context.options;
+    let target_path = long_path(if options.bucket_by_version {
+        context.output_dir.join(RESOURCES_BUCKET).join(context.package_mapper.map(rel_path))
+    } else {
+        context.output_dir.join(context.package_mapper.map(rel_path))
+    });
+    
+    // 获取文件大小
+    let file_size = source.metadata()
+        .with_context(|| format!("无法获取文件元数据: {:?}", source))?.len();
+    
+    let mut unchanged = false;
+    if !options.dry_run {
+        // 确保目标目录存在
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        // 复制文件
+        unchanged = !copy_resource_file(context, source, &target_path)?;
+        if let (Some(bar), false) = (context.progress, unchanged) {
+            bar.add_bytes(file_size);
+        }
+    }
+    
+    // 转换换行符后写入的文件与原文件大小不同，大小按输出目录中的文件记录
+    let size = if options.normalize_eol.is_some() && !options.dry_run {
+        target_path.metadata()
+            .with_context(|| format!("无法获取文件元数据: {:?}", target_path))?.len()
+    } else {
+        file_size
+    };
+    
+    if let Some(bar) = context.progress {
+        bar.inc(1);
+    } else if context.details {
+        println!("{}{}：{}，大小：{}", options.output_prefix(), label, rel_path.to_string_lossy(), options.display_size(size));
+    }
+    
+    let report = FileReport {
+        path: rel_path.to_string_lossy().into_owned(),
+        size,
+        // 转换换行符后写入的内容与源文件不同，按输出目录中的文件计算，演练模式下按源文件计算
+        sha256: checksum(if options.dry_run { source } else { &target_path }, options)?,
+    };
+    Ok((report, unchanged))
+}
+
+/// 单个源文件的class文件复制结果
+struct SourceCopyResult {
+    report: SourceReport,
+    /// 成功读取版本的class文件及其JDK版本
+    versions: Vec<(String, PathBuf)>,
+    /// 目标文件未变化而跳过复制的class文件数
+    unchanged: usize,
+    /// 因JDK版本不符合而跳过的class文件数
+    version_skipped: usize,
+    /// 修改时间早于--since而跳过的class文件数
+    since_skipped: usize,
+    /// 因--skip-synthetic跳过的合成类数
+    synthetic_skipped: usize,
+    /// 因--skip-invalid-class跳过的无效class文件数
+    invalid_skipped: usize,
+    /// 复制过程中产生的警告
+    warnings: Vec<Warning>,
+    /// 使用--keep-going时复制失败的class文件
+    failures: Vec<FileFailure>,
+    /// 该源文件的详细输出行
+    output: String,
+}
+
+/// 复制单个Java源文件对应的所有class文件并检查JDK版本
+///
+/// 该源文件的输出行随结果返回，由调用方按源文件路径顺序打印，避免并行复制时输出交错或顺序变化。
+fn copy_class_files(context: &CopyContext, java_rel_path: &Path, class_files: &[ClassLocation]) -> Result<SourceCopyResult> {
+    let options = context.options;
+    let java_file_name = java_rel_path.to_string_lossy();
+    let mut result = SourceCopyResult {
+        report: SourceReport {
+            source: java_file_name.clone().into_owned(),
+            sha256: None,
+            classes: Vec::new(),
+        },
+        versions: Vec::new(),
+        unchanged: 0,
+        version_skipped: 0,
+        since_skipped: 0,
+        synthetic_skipped: 0,
+        invalid_skipped: 0,
+        warnings: Vec::new(),
+        failures: Vec::new(),
+        output: String::from("----------------------------------------\n"),
+    };
+    
+    for location in class_files {
+        if let Err(err) = copy_class_file_entry(context, java_rel_path, location, &mut result) {
+            if !options.keep_going {
+                return Err(err);
+            }
+            result.failures.push(FileFailure::new(&location.rel_path, &err));
+            if let Some(bar) = context.progress {
+                bar.inc(1);
+            }
+        }
+    }
+    
+    if result.since_skipped > 0 && result.since_skipped == class_files.len() {
+        result.output.push_str(&format!("{}源文件：{}，所有class文件都早于指定时间，视为未变化\n", options.output_prefix(), java_file_name));
+    }
+    result.report.sha256 = checksum(&context.source_dir.join(java_rel_path), options)?;
+    Ok(result)
+}
+
+/// 检查并复制源文件对应的一个class文件，结果记录到`result`中
+fn copy_class_file_entry(context: &CopyContext, java_rel_path: &Path, location: &ClassLocation, result: &mut SourceCopyResult) -> Result<()> {
+    let CopyContext { output_dir, options, progress, package_mapper, class_renamer, color, .. } = *context;
+    let prefix = options.output_prefix();
+    let java_file_name = java_rel_path.to_string_lossy();
+    let class_file = &location.path();
+    let rel_path = location.rel_path.as_path();
+    
+    let target_rel_path = class_target_rel_path(location, options, context.class_prefixes, package_mapper, class_renamer);
+    
+    // 获取文件大小
+    let metadata = class_file.metadata()
+        .with_context(|| format!("无法获取文件元数据: {:?}", class_file))?;
+    let file_size = metadata.len();
+    
+    // 跳过上次部署之前编译的class文件
+    if let Some(since) = options.since {
+        let modified = metadata.modified()
+            .with_context(|| format!("无法获取文件修改时间: {:?}", class_file))?;
+        if modified < since {
+            result.output.push_str(&format!("{}源文件：{}，class文件：{}，修改时间早于指定时间，已跳过\n",
+                prefix,
+                java_file_name,
+                rel_path.to_string_lossy()
+            ));
+            result.since_skipped += 1;
+            if let Some(bar) = progress {
+                bar.inc(1);
+            }
+            return Ok(());
+        }
+    }
+    
+    // 跳过不是有效class文件的文件（如扩展名恰好为.class的其他文件）
+    let version = context.read_version(class_file);
+    if options.skip_invalid_class {
+        if let Err(err) = &version {
+            if err.is::<Error>() {
+                warn!("跳过无效的class文件: {:#}", err);
+                result.output.push_str(&format!("{}源文件：{}，class文件：{}，不是有效的class文件，已跳过\n",
+                    prefix,
+                    java_file_name,
+                    rel_path.to_string_lossy()
+                ));
+                result.invalid_skipped += 1;
+                if let Some(bar) = progress {
+                    bar.inc(1);
+                }
+                return Ok(());
+            }
+        }
+    }
+    
+    // 跳过编译器生成的合成类（如switch映射类）
+    if options.skip_synthetic && is_synthetic_class(class_file)? {
+        result.output.push_str(&format!("{}源文件：{}，class文件：{}，编译器生成的合成类，已跳过\n",
+            prefix,
+            java_file_name,
+            rel_path.to_string_lossy()
+        ));
+        result.synthetic_skipped += 1;
+        if let Some(bar) = progress {
+            bar.inc(1);
+        }
+        return Ok(());
+    }
+    
+    // 检查JDK版本
+    let mut version_report = None;
+    let jdk_version = match version {
+        Ok(version) => {
+            let v = version.to_jdk_version();
+            
+            version_report = Some(VersionReport {
+                major: version.major,
+                minor: version.minor,
+                jdk_version: v.clone(),
+                preview: version.is_preview(),
+            });
+            if version.is_preview() {
+                format!("{}（预览特性）", v)
+            } else {
+                v
+            }
+        },
+        // 格式无效的class文件直接中止操作
+        Err(err) if err.is::<Error>() => return Err(err),
+        Err(err) => {
+            result.warnings.push(Warning::new(WarningKind::MissingVersion, Some(rel_path),
+                format!("无法读取JDK版本: {:#}", err)));
+            "未知版本".to_string()
+        }
+    };
+    
+    // 只复制指定JDK版本的class文件
+    if let Some(only_jdk) = options.only_jdk {
+        let expected_major = JavaClassVersion::major_for_jdk(only_jdk);
+        if version_report.as_ref().is_none_or(|v| Some(v.major) != expected_major) {
+            result.output.push_str(&format!("{}源文件：{}，class文件：{}，JDK版本：{}，不是JDK {}，已跳过\n",
+                prefix,
+                java_file_name,
+                rel_path.to_string_lossy(),
+                jdk_version,
+                only_jdk
+            ));
+            result.version_skipped += 1;
+            if let Some(bar) = progress {
+                bar.inc(1);
+            }
+            return Ok(());
+        }
+    }
+    
+    // 按JDK版本分目录输出
+    let target_path = long_path(if options.bucket_by_version {
+        output_dir.join(version_bucket(version_report.as_ref())).join(target_rel_path)
+    } else {
+        output_dir.join(target_rel_path)
+    });
+    
+    // 检查class文件声明的包名是否与源文件路径一致，模块描述没有包名，改为读取模块名
+    let mut module_info = String::new();
+    let class_name = match read_class_name(class_file) {
+        Ok(class_name) if class_name == MODULE_INFO => {
+            match read_module_name(class_file) {
+                Ok(Some(module_name)) => module_info = format!("，模块：{}", module_name),
+                Ok(None) => {}
+                Err(err) => result.warnings.push(Warning::new(WarningKind::UnreadableClassName, Some(rel_path),
+                    format!("无法读取模块描述中的模块名: {:#}", err))),
+            }
+            Some(class_name)
+        }
+        Ok(class_name) => {
+            let declared = class_name.rsplit_once('.').map_or("", |(package, _)| package);
+            let expected = package_name(context.package_dir(java_rel_path));
+            if declared != expected {
+                result.warnings.push(Warning::new(WarningKind::PackageMismatch, Some(rel_path),
+                    format!("class文件声明的包名与源文件路径不一致: {}（声明: {}，期望: {}）",
+                        rel_path.to_string_lossy(),
+                        display_package(declared),
+                        display_package(&expected)
+                    )));
+            }
+            Some(class_name)
+        },
+        Err(err) => {
+            result.warnings.push(Warning::new(WarningKind::UnreadableClassName, Some(rel_path),
+                format!("无法读取class文件的类名: {:#}", err)));
+            None
+        }
+    };
+    
+    // 检查class文件记录的源文件名
+    if options.verify_source_attr {
+        let expected = java_rel_path.file_name().unwrap_or_default().to_string_lossy();
+        match read_source_file(class_file) {
+            Ok(Some(source_file)) if source_file != expected => {
+                result.warnings.push(Warning::new(WarningKind::SourceFileMismatch, Some(rel_path),
+                    format!("class文件的SourceFile属性与源文件不一致: {}（记录: {}，期望: {}）",
+                        rel_path.to_string_lossy(),
+                        source_file,
+                        expected
+                    )));
+            },
+            Ok(Some(_)) => {},
+            Ok(None) => info!("class文件没有SourceFile属性，跳过检查: {}", rel_path.to_string_lossy()),
+            Err(err) => result.warnings.push(Warning::new(WarningKind::UnreadableClassName, Some(rel_path),
+                format!("无法读取class文件的SourceFile属性: {:#}", err))),
+        }
+    }
+    
+    if version_report.as_ref().is_some_and(|version| version.preview) {
+        result.warnings.push(Warning::new(WarningKind::PreviewFeature, Some(rel_path),
+            format!("class文件使用了预览特性编译，只能在完全相同的JDK版本上运行: {}", rel_path.to_string_lossy())));
+    }
+    
+    if !options.dry_run {
+        // 确保目标目录存在
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        // 复制文件
+        if !copy_class_file(context, class_file, &target_path)? {
+            result.unchanged += 1;
+        } else if let Some(bar) = progress {
+            bar.add_bytes(file_size);
+        }
+    }
+    
+    // 去除调试信息后写入的文件比原文件小，大小按输出目录中的文件记录
+    let size = if options.strip_debug && !options.dry_run {
+        target_path.metadata()
+            .with_context(|| format!("无法获取文件元数据: {:?}", target_path))?.len()
+    } else {
+        file_size
+    };
+    
+    // 记录详细信息
+    let class_name_info = match (&class_name, options.show_class_names) {
+        (Some(class_name), true) => format!("，类名：{}", class_name),
+        (None, true) => "，类名：未知".to_string(),
+        (_, false) => String::new(),
+    };
+    let renamed_file_name = target_path.file_name().unwrap_or_default();
+    let rename_info = if renamed_file_name != rel_path.file_name().unwrap_or_default() {
+        format!("，重命名为：{}", renamed_file_name.to_string_lossy())
+    } else {
+        String::new()
+    };
+    let compiler = options.guess_compiler
+        .then(|| version_report.as_ref().map(|version| guess_compiler(version, read_source_file(class_file).ok().flatten().as_deref())))
+        .flatten();
+    let compiler_info = match &compiler {
+        Some(compiler) => format!("，可能由 {} 编译", compiler),
+        None => String::new(),
+    };
+    let release_info = match (location.release, options.multi_release) {
+        (Some(release), _) => format!("，多版本变体：META-INF/versions/{}", release),
+        (None, Some(_)) => "，多版本变体：基础版本".to_string(),
+        (None, None) => String::new(),
+    };
+    result.output.push_str(&format!("{}源文件：{}，class文件：{}{}{}{}{}，大小：{}，JDK版本：{}{}\n", 
+        prefix,
+        java_file_name, 
+        rel_path.to_string_lossy(), 
+        rename_info,
+        class_name_info,
+        module_info,
+        release_info,
+        options.display_size(size), 
+        if color {
+            colorize_version(&jdk_version, version_report.as_ref().map(|v| v.major), options.target_jdk)
+        } else {
+            jdk_version
+        },
+        compiler_info
+    ));
+    
+    // 记录版本信息，校验和按写入输出目录的内容计算（去除调试信息后与原文件不同），演练模式下按原文件计算
+    let sha256 = checksum(if options.dry_run { class_file } else { &target_path }, options)?;
+    if let Some(version) = &version_report {
+        result.versions.push((version.jdk_version.clone(), class_file.clone()));
+    }
+    let class_report = ClassReport {
+        path: rel_path.to_string_lossy().into_owned(),
+        size,
+        version: version_report,
+        sha256,
+        release: location.release,
+        compiler,
+    };
+    if let Some(stream) = context.report_stream {
+        stream.write_class(&java_file_name, &class_report)?;
+    }
+    result.report.classes.push(class_report);
+    if let Some(bar) = progress {
+        bar.inc(1);
+    }
+    Ok(())
+}
+
+/// 根据class文件版本、预览标志和SourceFile属性推测编译器，只是尽力而为的推断
+///
+/// SourceFile的扩展名为`.kt`、`.scala`、`.groovy`时视为对应语言的编译器，版本只表示目标JDK；
+/// 其他情况视为与class文件版本对应的javac，JDK 8及以前使用`1.N`的版本号。
+fn guess_compiler(version: &VersionReport, source_file: Option<&str>) -> String {
+    let jdk = version.major.saturating_sub(44);
+    let other_compiler = source_file
+        .and_then(|source_file| source_file.rsplit_once('.'))
+        .and_then(|(_, ext)| match ext {
+            "kt" => Some("kotlinc"),
+            "scala" => Some("scalac"),
+            "groovy" => Some("groovyc"),
+            _ => None,
+        });
+    let mut compiler = match other_compiler {
+        Some(compiler) => format!("{}（目标JDK {}）", compiler, jdk),
+        None if jdk <= 8 => format!("javac 1.{}", jdk),
+        None => format!("javac {}", jdk),
+    };
+    if version.preview {
+        compiler.push_str("（预览）");
+    }
+    compiler
+}
+
+/// 解析`--since`的时间，支持RFC 3339格式（如`2024-05-01T12:00:00Z`）或Unix时间戳（秒）
+pub fn parse_timestamp(text: &str) -> std::result::Result<SystemTime, String> {
+    if let Ok(secs) = text.parse::<u64>() {
+        return Ok(UNIX_EPOCH + Duration::from_secs(secs));
+    }
+    humantime::parse_rfc3339_weak(text)
+        .map_err(|_| format!("无效的时间: {}，应为RFC 3339格式（如2024-05-01T12:00:00Z）或Unix时间戳", text))
+}
+
+/// 根据包目录推断包名，如`com/example` -> `com.example`
+fn package_name(package_dir: &Path) -> String {
+    package_dir.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// 用于显示的包名，默认包显示为"（默认包）"
+fn display_package(package: &str) -> &str {
+    if package.is_empty() { "（默认包）" } else { package }
+}
+
+/// 将字节数格式化为人类可读的大小，如`512 字节`、`1.5 KiB`、`2.0 MiB`
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if size < 1024 {
+        return format!("{} 字节", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// 无法获取终端宽度时（如输出被重定向）条形图使用的宽度
+const HISTOGRAM_DEFAULT_WIDTH: usize = 80;
+
+/// 从[`JavaClassVersion::to_jdk_version`]生成的版本名推算主版本号，用于排序
+fn major_of_jdk_version(version: &str) -> Option<u16> {
+    if let Some(major) = version.strip_prefix("未知JDK版本 (major: ").and_then(|rest| rest.strip_suffix(')')) {
+        return major.parse().ok();
+    }
+    let number = version.strip_prefix("JDK ")?;
+    match number.strip_prefix("1.") {
+        Some(minor) => minor.parse::<u16>().ok().map(|minor| minor + 44),
+        None => number.parse::<u16>().ok().map(|jdk| jdk + 44),
+    }
+}
+
+/// 格式化耗时，1秒以下显示为毫秒，否则显示为保留一位小数的秒数
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_secs(1) {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+/// Windows路径长度限制（MAX_PATH）
+const MAX_PATH: usize = 260;
+
+/// 在Windows上为超过[`MAX_PATH`]的绝对路径加上`\\?\`扩展长度前缀，避免复制和创建目录失败，其他平台原样返回
+fn long_path(path: PathBuf) -> PathBuf {
+    if !cfg!(windows) || !path.is_absolute() || path.as_os_str().len() <= MAX_PATH {
+        return path;
+    }
+    match path.to_str() {
+        Some(text) => PathBuf::from(extended_length_path(text)),
+        None => path,
+    }
+}
+
+/// 为Windows绝对路径加上扩展长度前缀，UNC路径`\\server\share`转换为`\\?\UNC\server\share`
+///
+/// 扩展长度路径不会再被规范化，因此同时将`/`替换为`\`。
+fn extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    match path.strip_prefix(r"\\") {
+        Some(unc) => format!(r"\\?\UNC\{}", unc),
+        None => format!(r"\\?\{}", path),
+    }
+}
+
+/// 复制单个文件，可选保留源文件的修改时间
+///
+/// 目标文件与源文件相同时跳过复制并返回false。使用`hardlink`时先尝试创建硬链接，失败时记录下来并改为复制。
+fn copy_file(context: &CopyContext, source: &Path, target: &Path) -> Result<bool> {
+    let options = context.options;
+    if options.symlink {
+        return symlink_file(source, target);
+    }
+    
+    // 之前使用--symlink生成的链接替换为复制的文件，不能写入链接指向的源文件
+    let replaced_link = remove_symlink(target)?;
+    if !replaced_link && is_unchanged(source, target, options.verify_hash)? {
+        return Ok(false);
+    }
+    
+    // 硬链接与源文件共用同一份数据和修改时间，无需再设置修改时间
+    if options.hardlink {
+        match hard_link(source, target) {
+            Ok(()) => return Ok(true),
+            Err(err) => {
+                warn!("无法创建硬链接: {:?} -> {:?}: {}，改为复制", source, target, err);
+                context.hardlink_fallbacks.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+    
+    unlink_target(target)
+        .and_then(|()| copy_with_retry(source, target, options))
+        .with_context(|| format!("复制文件失败: {:?} -> {:?}", source, target))?;
+    
+    if options.preserve_timestamps {
+        preserve_timestamp(source, target)?;
+    }
+    
+    Ok(true)
+}
+
+/// 复制class文件，启用`strip_debug`时写入去除调试信息后的内容
+///
+/// 去除调试信息时目标文件与改写后的内容相同才跳过复制并返回false。
+fn copy_class_file(context: &CopyContext, source: &Path, target: &Path) -> Result<bool> {
+    let options = context.options;
+    if !options.strip_debug {
+        return copy_file(context, source, target);
+    }
+    
+    let bytes = fs::read(source)
+        .with_context(|| format!("无法读取class文件: {:?}", source))?;
+    let stripped = classfile::strip_debug_info(&bytes)
+        .with_context(|| format!("去除调试信息失败: {:?}", source))?;
+    if !remove_symlink(target)? && fs::read(target).is_ok_and(|existing| existing == stripped) {
+        return Ok(false);
+    }
+    
+    unlink_target(target)
+        .and_then(|()| write_with_retry(source, target, &stripped, options))
+        .with_context(|| format!("写入文件失败: {:?}", target))?;
+    if options.preserve_timestamps {
+        preserve_timestamp(source, target)?;
+    }
+    
+    Ok(true)
+}
+
+/// 复制资源文件，启用`normalize_eol`时转换文本文件的换行符后写入
+///
+/// 扩展名不在文本文件列表中、或内容含有NUL字节（视为二进制文件）时原样复制。
+fn copy_resource_file(context: &CopyContext, source: &Path, target: &Path) -> Result<bool> {
+    let options = context.options;
+    let Some(eol) = options.normalize_eol.filter(|_| is_text_file(source, options)) else {
+        return copy_file(context, source, target);
+    };
+    
+    let bytes = fs::read(source)
+        .with_context(|| format!("无法读取文件: {:?}", source))?;
+    if bytes.iter().take(BINARY_CHECK_LEN).any(|&byte| byte == 0) {
+        return copy_file(context, source, target);
+    }
+    let normalized = normalize_line_endings(&bytes, eol);
+    if !remove_symlink(target)? && fs::read(target).is_ok_and(|existing| existing == normalized) {
+        return Ok(false);
+    }
+    
+    unlink_target(target)
+        .and_then(|()| write_with_retry(source, target, &normalized, options))
+        .with_context(|| format!("写入文件失败: {:?}", target))?;
+    if options.preserve_timestamps {
+        preserve_timestamp(source, target)?;
+    }
+    
+    Ok(true)
+}
+
+/// 判断二进制文件时检查的字节数
+const BINARY_CHECK_LEN: usize = 8000;
+
+/// 按扩展名判断是否为需要转换换行符的文本文件
+fn is_text_file(path: &Path, options: &Options) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    if options.text_exts.is_empty() {
+        DEFAULT_TEXT_EXTENSIONS.iter().any(|text_ext| text_ext.eq_ignore_ascii_case(ext))
+    } else {
+        options.text_exts.iter().any(|text_ext| text_ext.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    }
+}
+
+/// 将`\r\n`和`\n`统一转换为`eol`，单独的`\r`保持不变
+fn normalize_line_endings(bytes: &[u8], eol: LineEnding) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        match byte {
+            // `\r\n`中的`\r`由后面的`\n`统一处理
+            b'\r' if iter.peek() == Some(&&b'\n') => {},
+            b'\n' => {
+                if eol == LineEnding::Crlf {
+                    output.push(b'\r');
+                }
+                output.push(b'\n');
+            },
+            _ => output.push(byte),
+        }
+    }
+    output
+}
+
+/// 将目标文件的修改时间设置为源文件的修改时间
+fn preserve_timestamp(source: &Path, target: &Path) -> Result<()> {
+    let modified = source.metadata()
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("无法获取文件修改时间: {:?}", source))?;
+    filetime::set_file_mtime(target, FileTime::from_system_time(modified))
+        .with_context(|| format!("无法设置文件修改时间: {:?}", target))
+}
+
+/// 在`target`创建指向源文件绝对路径的符号链接，已经指向该文件时返回false
+///
+/// 目标位置已有文件或其他链接时先删除。在Windows上创建符号链接需要开发者模式或管理员权限。
+fn symlink_file(source: &Path, target: &Path) -> Result<bool> {
+    let source = fs::canonicalize(source)
+        .with_context(|| format!("无法解析路径: {:?}", source))?;
+    if fs::read_link(target).is_ok_and(|existing| existing == source) {
+        return Ok(false);
+    }
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target)
+            .with_context(|| format!("删除失败: {:?}", target))?;
+    }
+    
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&source, target);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_file(&source, target);
+    result.with_context(|| format!("创建符号链接失败: {:?} -> {:?}", target, source))?;
+    Ok(true)
+}
+
+/// 目标位置是符号链接时删除链接本身，返回是否删除
+fn remove_symlink(target: &Path) -> Result<bool> {
+    if !fs::symlink_metadata(target).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        return Ok(false);
+    }
+    fs::remove_file(target)
+        .with_context(|| format!("删除失败: {:?}", target))?;
+    Ok(true)
+}
+
+/// 为源文件创建硬链接，目标文件已存在时先删除，避免改写与之共用数据的其他文件
+fn hard_link(source: &Path, target: &Path) -> std::io::Result<()> {
+    unlink_target(target)?;
+    fs::hard_link(source, target)
+}
+
+/// 删除目标位置已有的文件或链接，之后写入的是新文件
+///
+/// 之前使用`--hardlink`生成的输出与原文件共用数据，直接写入会同时改写原文件。
+fn unlink_target(target: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target)?;
+    }
+    Ok(())
+}
+
+/// 复制文件，遇到暂时性IO错误时按指数退避重试，权限不足等其他错误直接返回
+fn copy_with_retry(source: &Path, target: &Path, options: &Options) -> std::io::Result<u64> {
+    with_retry(source, target, options, || match options.buffer_size {
+        Some(buffer_size) => buffered_copy(source, target, buffer_size),
+        None => fs::copy(source, target),
+    })
+}
+
+/// 将改写后的内容写入目标文件，与复制文件一样按`--buffer-size`分块写入，遇到暂时性IO错误时重试
+fn write_with_retry(source: &Path, target: &Path, content: &[u8], options: &Options) -> std::io::Result<()> {
+    with_retry(source, target, options, || match options.buffer_size {
+        Some(buffer_size) => buffered_write(target, content, buffer_size),
+        None => fs::write(target, content),
+    })
+}
+
+/// 执行复制或写入操作，遇到暂时性IO错误时按指数退避重试，`source`和`target`只用于输出日志
+fn with_retry<T>(source: &Path, target: &Path, options: &Options, mut operation: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+    let mut delay = std::time::Duration::from_millis(options.retry_delay_ms);
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Err(err) if attempt < options.copy_retries && is_transient(&err) => {
+                attempt += 1;
+                warn!("复制文件失败: {:?} -> {:?}: {}，{} 毫秒后第 {}/{} 次重试",
+                    source, target, err, delay.as_millis(), attempt, options.copy_retries);
+                std::thread::sleep(delay);
+                delay *= 2;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// 使用`buffer_size`字节的缓冲区逐块复制文件，与`fs::copy`一样复制权限并返回复制的字节数
+///
+/// 用于调整网络文件系统上每次读写的大小，修改时间等由调用方按选项另外设置。
+fn buffered_copy(source: &Path, target: &Path, buffer_size: usize) -> std::io::Result<u64> {
+    use std::io::Write;
+    
+    let mut reader = fs::File::open(source)?;
+    let permissions = reader.metadata()?.permissions();
+    let mut writer = fs::File::create(target)?;
+    let mut buffer = vec![0; buffer_size];
+    let mut copied = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    writer.flush()?;
+    fs::set_permissions(target, permissions)?;
+    Ok(copied)
+}
+
+/// 使用每块不超过`buffer_size`字节的写入调用写入文件
+fn buffered_write(target: &Path, content: &[u8], buffer_size: usize) -> std::io::Result<()> {
+    use std::io::Write;
+    
+    let mut writer = fs::File::create(target)?;
+    for chunk in content.chunks(buffer_size) {
+        writer.write_all(chunk)?;
+    }
+    writer.flush()
+}
+
+/// 判断IO错误是否可能是暂时性的（如网络存储超时），重试后可能成功
+fn is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(err.kind(),
+        ErrorKind::WouldBlock
+        | ErrorKind::TimedOut
+        | ErrorKind::Interrupted
+        | ErrorKind::ResourceBusy
+        | ErrorKind::StaleNetworkFileHandle
+        | ErrorKind::ConnectionReset
+        | ErrorKind::ConnectionAborted)
+}
+
+/// 判断目标文件是否与源文件相同
+///
+/// 默认比较文件大小，并要求目标文件不早于源文件修改；`verify_hash`为true时改为比较内容的SHA-256。
+fn is_unchanged(source: &Path, target: &Path, verify_hash: bool) -> Result<bool> {
+    let Ok(target_metadata) = target.metadata() else {
+        return Ok(false);
+    };
+    let source_metadata = source.metadata()
+        .with_context(|| format!("无法获取文件元数据: {:?}", source))?;
+    if source_metadata.len() != target_metadata.len() {
+        return Ok(false);
+    }
+    
+    if verify_hash {
+        return Ok(file_sha256(source)? == file_sha256(target)?);
+    }
+    
+    match (source_metadata.modified(), target_metadata.modified()) {
+        (Ok(source_modified), Ok(target_modified)) => Ok(target_modified >= source_modified),
+        _ => Ok(false),
+    }
+}
+
+/// 启用--checksums时计算文件的SHA-256十六进制字符串
+fn checksum(path: &Path, options: &Options) -> Result<Option<String>> {
+    if !options.checksums && options.inventory.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(to_hex(&file_sha256(path)?)))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// 流式计算文件内容的SHA-256
+fn file_sha256(path: &Path) -> Result<[u8; 32]> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("无法打开文件: {:?}", path))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("读取文件失败: {:?}", path))?;
+    Ok(hasher.finalize().into())
+}
+
+/// 将报告以格式化的JSON写入指定文件
+fn write_report(path: &Path, summary: &Summary) -> Result<()> {
+    let report = CopyReport {
+        sources: &summary.sources,
+        non_java_files: &summary.non_java_files,
+        class_resources: &summary.class_resources,
+        copied_sources: &summary.copied_sources,
+        source_count: summary.source_count,
+        class_count: summary.class_count,
+        classes_per_source: summary.classes_per_source(),
+        non_java_count: summary.non_java_count,
+        class_resource_count: summary.class_resource_count,
+        copied_source_count: summary.copied_source_count,
+        total_copied: summary.total_copied(),
+        total_bytes: summary.total_bytes(),
+        unchanged_count: summary.unchanged_count,
+        version_skipped_count: summary.version_skipped_count,
+        since_skipped_count: summary.since_skipped_count,
+        synthetic_skipped_count: summary.synthetic_skipped_count,
+        inner_skipped_count: summary.inner_skipped_count,
+        invalid_skipped_count: summary.invalid_skipped_count,
+        missing_source_count: summary.missing_source_count,
+        unchanged_source_count: summary.unchanged_source_count,
+        deduped_count: summary.deduped_count,
+        conflict_count: summary.conflict_count,
+        class_sha256: summary.class_sha256(),
+        jdk_versions: summary.jdk_versions.iter()
+            .map(|(version, files)| (version.as_str(), files.len()))
+            .collect(),
+        failures: &summary.failures,
+    };
+    let json = serde_json::to_string_pretty(&report)
+        .context("序列化JSON报告失败")?;
+    fs::write(path, json)
+        .with_context(|| format!("写入JSON报告失败: {:?}", path))?;
+    Ok(())
+}
+
+/// 在读取线程池中并行读取所有class文件的版本，按JDK版本归类
+///
+/// 无法读取版本的class文件记录为警告，格式无效的class文件直接返回错误。
+fn audit_class_versions(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, options: &Options, warnings: &mut Vec<Warning>) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+    let locations: Vec<&ClassLocation> = source_to_classes.values().flatten().collect();
+    let versions: Vec<_> = options.read_pool()?.install(|| {
+        locations.par_iter()
+            .map(|location| (location, read_class_file_version(&location.path())))
+            .collect()
+    });
+    
+    let mut jdk_versions: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (location, version) in versions {
+        match version {
+            Ok(version) => jdk_versions.entry(version.to_jdk_version())
+                .or_default()
+                .push(location.path()),
+            Err(err) if err.is::<Error>() && !options.skip_invalid_class => return Err(err),
+            Err(err) => warnings.push(Warning::new(WarningKind::MissingVersion, Some(&location.rel_path),
+                format!("无法读取JDK版本: {:#}", err))),
+        }
+    }
+    Ok(jdk_versions)
+}
+
+/// 在读取线程池中读取将要复制的class文件的版本，按JDK版本归类
+///
+/// 与复制时一样排除早于`--since`、被`--skip-synthetic`跳过或不符合`--only-jdk`的class文件；
+/// 无法读取版本的class文件不归类，复制时再报错、跳过或给出警告。
+fn versions_to_copy(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, options: &Options) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+    let locations: Vec<&ClassLocation> = source_to_classes.values().flatten().collect();
+    let versions = options.read_pool()?.install(|| {
+        locations.par_iter()
+            .map(|location| -> Result<Option<(String, PathBuf)>> {
+                let path = location.path();
+                if let Some(since) = options.since {
+                    let modified = path.metadata()
+                        .and_then(|metadata| metadata.modified())
+                        .with_context(|| format!("无法获取文件修改时间: {:?}", path))?;
+                    if modified < since {
+                        return Ok(None);
+                    }
+                }
+                let Ok(version) = read_class_file_version(&path) else {
+                    return Ok(None);
+                };
+                if options.skip_synthetic && is_synthetic_class(&path)? {
+                    return Ok(None);
+                }
+                if options.only_jdk.is_some_and(|only_jdk| Some(version.major) != JavaClassVersion::major_for_jdk(only_jdk)) {
+                    return Ok(None);
+                }
+                Ok(Some((version.to_jdk_version(), path)))
+            })
+            .collect::<Result<Vec<_>>>()
+    })?;
+    
+    let mut jdk_versions: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for (version, path) in versions.into_iter().flatten() {
+        jdk_versions.entry(version).or_default().push(path);
+    }
+    Ok(jdk_versions)
+}
+
+/// 检查按版本归类的class文件是否都不高于目标JDK版本，列出超出的文件
+fn check_target_jdk(jdk_versions: &BTreeMap<String, Vec<PathBuf>>, target_jdk: u16) -> Result<()> {
+    let max_major = JavaClassVersion::major_for_jdk(target_jdk);
+    let mut too_new = 0;
+    for (version, files) in jdk_versions {
+        if major_of_jdk_version(version).zip(max_major).is_some_and(|(major, max_major)| major > max_major) {
+            for file in files {
+                error!("class文件版本超出目标JDK {}: {}（{}）", target_jdk, file.display(), version);
+            }
+            too_new += files.len();
+        }
+    }
+    if too_new > 0 {
+        bail!("{} 个class文件的JDK版本高于目标版本 JDK {}", too_new, target_jdk);
+    }
+    Ok(())
+}
+
+/// 检测到多个不同的JDK版本时发出警告
+fn warn_mixed_versions(jdk_versions: &BTreeMap<String, Vec<PathBuf>>, warnings: &mut Vec<Warning>) {
+    if jdk_versions.len() > 1 {
+        let mut versions: Vec<_> = jdk_versions.keys().map(String::as_str).collect();
+        versions.sort();
+        warnings.push(Warning::new(WarningKind::VersionMismatch, None,
+            format!("检测到多个不同的JDK版本: {}", versions.join(", "))));
+    }
+}
+
+/// 按源文件路径顺序打印每个源文件对应的class文件，格式与文本清单相同，找不到class文件的源文件也会列出
+fn print_resolutions(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, missing: &[PathBuf]) {
+    let mut lines: BTreeMap<&Path, String> = source_to_classes.iter()
+        .map(|(java_rel_path, class_files)| {
+            let class_names: Vec<_> = class_files.iter()
+                .map(|location| location.rel_path.to_string_lossy())
+                .collect();
+            (java_rel_path.as_path(), class_names.join(", "))
+        })
+        .collect();
+    for java_rel_path in missing {
+        lines.insert(java_rel_path, "（找不到class文件）".to_string());
+    }
+    for (java_rel_path, classes) in &lines {
+        println!("{} -> {}", java_rel_path.to_string_lossy(), classes);
+    }
+    
+    let class_count: usize = source_to_classes.values().map(Vec::len).sum();
+    println!("\n共 {} 个源文件，{} 个class文件，{} 个源文件找不到class文件", lines.len(), class_count, missing.len());
+}
+
+/// 将源文件到class文件的对应关系写入文本清单，非Java文件列在末尾
+///
+/// 每行格式为`com/example/Foo.java -> com/example/Foo.class, com/example/Foo$Inner.class`。
+fn write_manifest(path: &Path, source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, non_java_files: &[FileReport], class_resources: &[FileReport]) -> Result<()> {
+    let mut content = String::new();
+    for (java_rel_path, class_files) in source_to_classes {
+        let class_names: Vec<_> = class_files.iter()
+            .map(|location| location.rel_path.to_string_lossy())
+            .collect();
+        content.push_str(&format!("{} -> {}\n", java_rel_path.to_string_lossy(), class_names.join(", ")));
+    }
+    
+    for (title, files) in [("非Java文件", non_java_files), ("class目录资源文件", class_resources)] {
+        if files.is_empty() {
+            continue;
+        }
+        content.push_str(&format!("\n# {}\n", title));
+        for file in files {
+            content.push_str(&file.path);
+            content.push('\n');
+        }
+    }
+    
+    fs::write(path, content)
+        .with_context(|| format!("写入清单失败: {:?}", path))?;
+    Ok(())
+}
+
+/// 文件清册的表头
+const INVENTORY_HEADER: &str = "path,size,sha256,jdk_version,kind,source";
+
+/// 将所有复制的文件写入CSV格式的文件清册，每行一个文件
+///
+/// 依次列出class文件、非Java文件、class目录资源文件和源文件，非class文件的`jdk_version`和`source`为空。
+fn write_inventory(path: &Path, summary: &Summary) -> Result<()> {
+    let mut content = format!("{}\n", INVENTORY_HEADER);
+    let mut push_row = |fields: [&str; 6]| {
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        content.push_str(&fields.join(","));
+        content.push('\n');
+    };
+    
+    for source in &summary.sources {
+        for class in &source.classes {
+            push_row([
+                &class.path,
+                &class.size.to_string(),
+                class.sha256.as_deref().unwrap_or_default(),
+                class.version.as_ref().map_or("", |version| version.jdk_version.as_str()),
+                "class",
+                &source.source,
+            ]);
+        }
+    }
+    for (kind, files) in [("non_java", &summary.non_java_files), ("resource", &summary.class_resources), ("source", &summary.copied_sources)] {
+        for file in files {
+            push_row([&file.path, &file.size.to_string(), file.sha256.as_deref().unwrap_or_default(), "", kind, ""]);
+        }
+    }
+    
+    fs::write(path, content)
+        .with_context(|| format!("写入文件清册失败: {:?}", path))?;
+    Ok(())
+}
+
+/// 按RFC 4180转义CSV字段，包含逗号、引号或换行时加引号
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 基于glob模式的源文件过滤规则，排除规则优先于包含规则
+struct PathFilter {
+    includes: Option<GlobSet>,
+    excludes: GlobSet,
+}
+
+impl PathFilter {
+    fn new(includes: &[String], excludes: &[String]) -> Result<Self> {
+        let includes = if includes.is_empty() {
+            None
+        } else {
+            Some(build_globset(includes)?)
+        };
+        Ok(PathFilter {
+            includes,
+            excludes: build_globset(excludes)?,
+        })
+    }
+    
+    /// 判断相对路径是否应当被处理
+    fn is_match(&self, rel_path: &Path) -> bool {
+        if self.excludes.is_match(rel_path) {
+            return false;
+        }
+        self.includes.as_ref().is_none_or(|includes| includes.is_match(rel_path))
+    }
+    
+    /// 过滤文件列表，匹配时使用相对于base_dir的路径
+    fn apply(&self, base_dir: &Path, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        files.into_iter()
+            .filter(|path| path.strip_prefix(base_dir).is_ok_and(|rel_path| self.is_match(rel_path)))
+            .collect()
+    }
+}
+
+/// 复制时重定位包目录的规则集合
+struct PackageMapper {
+    rules: Vec<PackageRule>,
+}
+
+/// 一条包目录重定位规则
+struct PackageRule {
+    /// 规则的原始文本，用于警告信息
+    text: String,
+    from: PathBuf,
+    to: PathBuf,
+    /// 规则是否匹配过任何文件
+    matched: AtomicBool,
+}
+
+impl PackageMapper {
+    /// 解析`from=to`形式的规则，包名可以用`.`或`/`分隔
+    fn new(mappings: &[String]) -> Result<Self> {
+        let package_path = |package: &str| -> PathBuf {
+            package.split(['.', '/']).filter(|part| !part.is_empty()).collect()
+        };
+        let rules = mappings.iter()
+            .map(|mapping| {
+                let (from, to) = mapping.split_once('=')
+                    .with_context(|| format!("无效的包映射: {}，格式应为 from=to", mapping))?;
+                let from = package_path(from);
+                if from.as_os_str().is_empty() {
+                    bail!("无效的包映射: {}，原包名不能为空", mapping);
+                }
+                Ok(PackageRule {
+                    text: mapping.clone(),
+                    from,
+                    to: package_path(to),
+                    matched: Default::default(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(PackageMapper { rules })
+    }
+    
+    /// 返回重定位后的相对路径，多条规则匹配时使用最长的原包名
+    fn map(&self, rel_path: &Path) -> PathBuf {
+        let rule = self.rules.iter()
+            .filter(|rule| rel_path.parent().is_some_and(|parent| parent.starts_with(&rule.from)))
+            .max_by_key(|rule| rule.from.components().count());
+        match rule {
+            Some(rule) => {
+                rule.matched.store(true, Ordering::Relaxed);
+                let rest = rel_path.strip_prefix(&rule.from).unwrap_or(rel_path);
+                rule.to.join(rest)
+            },
+            None => rel_path.to_path_buf(),
+        }
+    }
+    
+    /// 对没有匹配任何文件的规则发出警告
+    fn warn_unmatched(&self, warnings: &mut Vec<Warning>) {
+        for rule in &self.rules {
+            if !rule.matched.load(Ordering::Relaxed) {
+                warnings.push(Warning::new(WarningKind::UnmatchedMapping, None,
+                    format!("包映射 {} 没有匹配任何文件", rule.text)));
+            }
+        }
+    }
+}
+
+/// 复制时重命名class文件的规则集合
+struct ClassRenamer {
+    rules: Vec<(Regex, String)>,
+}
+
+impl ClassRenamer {
+    /// 解析`regex=replacement`形式的规则，replacement中可以用`$1`、`${name}`引用捕获组
+    fn new(renames: &[String]) -> Result<Self> {
+        let rules = renames.iter()
+            .map(|rename| {
+                let (pattern, replacement) = rename.split_once('=')
+                    .with_context(|| format!("无效的重命名规则: {}，格式应为 regex=replacement", rename))?;
+                let regex = Regex::new(pattern)
+                    .with_context(|| format!("无效的重命名规则: {}，正则表达式无效", rename))?;
+                Ok((regex, replacement.to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(ClassRenamer { rules })
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+    
+    /// 重命名class文件名，使用第一条匹配外部类名的规则
+    ///
+    /// 规则只作用于第一个`$`之前的外部类名，内部类后缀和扩展名保持不变，
+    /// 因此`Foo.class`和`Foo$Inner.class`会一致地重命名为`FooImpl.class`和`FooImpl$Inner.class`。
+    fn rename_file_name(&self, file_name: &OsStr) -> OsString {
+        let Some(stem) = file_name.to_str().and_then(|name| name.strip_suffix(".class")) else {
+            return file_name.to_os_string();
+        };
+        let (outer_name, suffix) = stem.find('$').map_or((stem, ""), |index| stem.split_at(index));
+        match self.rules.iter().find(|(regex, _)| regex.is_match(outer_name)) {
+            Some((regex, replacement)) => {
+                format!("{}{}.class", regex.replace(outer_name, replacement.as_str()), suffix).into()
+            },
+            None => file_name.to_os_string(),
+        }
+    }
+    
+    /// 返回重命名后的相对路径，目录部分不变
+    fn rename(&self, rel_path: &Path) -> PathBuf {
+        match rel_path.file_name() {
+            Some(file_name) if !self.is_empty() => rel_path.with_file_name(self.rename_file_name(file_name)),
+            _ => rel_path.to_path_buf(),
+        }
+    }
+}
+
+/// 将glob模式列表编译为GlobSet
+fn build_globset(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).with_context(|| format!("无效的glob模式: {}", pattern))?);
+    }
+    builder.build().context("构建glob规则失败")
+}
+
+/// 源代码目录（及其子目录）中的忽略文件名，使用gitignore语法
+pub const IGNORE_FILE_NAME: &str = ".srcclassignore";
+
+/// 收集指定目录下的所有源文件，返回Java文件（包括`lookup.source_exts`指定的其他源文件）和非Java文件的列表
+///
+/// 遍历时应用各目录中的`.srcclassignore`，子目录中的规则只作用于该子目录；忽略文件本身不会被收集。
+#[instrument(level = "debug", skip_all, fields(source_dir = %source_dir.display()))]
+pub fn collect_source_files(source_dir: &Path, lookup: &LookupOptions) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut java_files = Vec::new();
+    let mut non_java_files = Vec::new();
+    
+    // 只应用.srcclassignore，不读取.gitignore等其他忽略规则，也不跳过隐藏文件
+    let walker = ignore::WalkBuilder::new(source_dir)
+        .standard_filters(false)
+        .parents(false)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .follow_links(lookup.follow_links)
+        .max_depth(lookup.source_max_depth)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    for entry in walker {
+        let entry = entry.with_context(|| format!("遍历源代码目录失败: {:?}", source_dir))?;
+        let path = entry.path();
+        
+        if path.is_file() && path.file_name() != Some(OsStr::new(IGNORE_FILE_NAME)) {
+            if lookup.is_source_file(path) {
+                java_files.push(path.to_path_buf());
+            } else {
+                non_java_files.push(path.to_path_buf());
+            }
+        }
+    }
+    
+    Ok((java_files, non_java_files))
+}
+
+/// 映射文件中的一项：源文件及其class文件，路径分别相对于源代码目录和class目录
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingEntry {
+    source: PathBuf,
+    classes: Vec<PathBuf>,
+}
+
+/// 读取JSON格式的对应关系（`[{"source": "...", "classes": ["..."]}]`），按顺序在各class目录中定位每个class文件
+///
+/// 只检查列出的文件是否存在，不遍历任何目录；`classes`为空的源文件视为找不到class文件。
+fn read_mapping(path: &Path, class_dirs: &[PathBuf]) -> Result<BTreeMap<PathBuf, Vec<ClassLocation>>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("无法读取映射文件: {:?}", path))?;
+    let entries: Vec<MappingEntry> = serde_json::from_str(&content)
+        .with_context(|| format!("映射文件格式无效: {:?}", path))?;
+    
+    let mut mapping = BTreeMap::new();
+    for entry in entries {
+        if entry.source.is_absolute() {
+            bail!("映射文件中的源文件路径必须相对于源代码目录: {:?}", entry.source);
+        }
+        let mut locations = Vec::with_capacity(entry.classes.len());
+        for rel_path in entry.classes {
+            if rel_path.is_absolute() {
+                bail!("映射文件中的class文件路径必须相对于class目录: {:?}", rel_path);
+            }
+            let class_dir = class_dirs.iter()
+                .find(|class_dir| class_dir.join(&rel_path).is_file())
+                .with_context(|| format!("映射文件中的class文件在所有class目录中都不存在: {:?}（源文件 {:?}）", rel_path, entry.source))?;
+            locations.push(ClassLocation { class_dir: class_dir.clone(), rel_path, release: None });
+        }
+        locations.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+        if mapping.insert(entry.source.clone(), locations).is_some() {
+            bail!("映射文件中的源文件重复: {:?}", entry.source);
+        }
+    }
+    Ok(mapping)
+}
+
+/// 从文件列表读取源文件，返回Java文件和非Java文件的列表，`list`为`-`时从标准输入读取
+///
+/// 每行一个路径，空行和以`#`开头的行忽略。相对路径相对于源代码目录，绝对路径必须位于源代码目录下。
+pub fn read_source_list(source_dir: &Path, list: &Path, lookup: &LookupOptions) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let content = if list == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).context("无法从标准输入读取源文件列表")?
+    } else {
+        fs::read_to_string(list)
+            .with_context(|| format!("无法读取源文件列表: {:?}", list))?
+    };
+    let absolute_source_dir = std::path::absolute(source_dir)
+        .with_context(|| format!("无法解析路径: {:?}", source_dir))?;
+    
+    let mut java_files = Vec::new();
+    let mut non_java_files = Vec::new();
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let line_path = Path::new(line);
+        let rel_path = if line_path.is_absolute() {
+            line_path.strip_prefix(&absolute_source_dir)
+                .with_context(|| format!("源文件列表中的文件不在源代码目录下: {}", line))?
+        } else {
+            line_path
+        };
+        
+        let path = source_dir.join(rel_path);
+        if !path.is_file() {
+            bail!("源文件列表中的文件不存在: {}", line);
+        }
+        if lookup.is_source_file(&path) {
+            java_files.push(path);
+        } else {
+            non_java_files.push(path);
+        }
+    }
+    
+    Ok((java_files, non_java_files))
+}
+
+/// 查找Java文件对应的所有class文件，按文件名推断类名
+pub fn find_class_files(class_dir: &Path, java_rel_path: &Path, lookup: &LookupOptions) -> Result<Vec<PathBuf>> {
+    // 将Java路径转换为可能的class路径
+    let java_file_name = java_rel_path.file_stem()
+        .with_context(|| format!("无法获取文件名: {:?}", java_rel_path))?;
+    
+    let package_path = java_rel_path.parent().unwrap_or(Path::new(""));
+    find_class_files_for_types(class_dir, package_path, &[java_file_name], lookup)
+}
+
+/// 查找包目录下指定类型及其内部类对应的所有class文件
+#[instrument(level = "debug", skip_all, fields(class_dir = %class_dir.display(), package = %package_path.display()))]
+pub fn find_class_files_for_types(class_dir: &Path, package_path: &Path, type_names: &[impl AsRef<OsStr>], lookup: &LookupOptions) -> Result<Vec<PathBuf>> {
+    let mut class_files = Vec::new();
+    
+    let class_dir_with_package = class_dir.join(package_path);
+    
+    // 如果类路径不存在，返回空列表
+    if !class_dir_with_package.exists() {
+        return Ok(vec![]);
+    }
+    
+    // 处理内部类的情况（查找所有BaseClass.class, BaseClass$1.class, BaseClass$InnerClass.class等）
+    // 深度大于1时也查找包目录的子目录中同名的class文件
+    let max_depth = lookup.class_max_depth.unwrap_or(1);
+    for entry in WalkDir::new(&class_dir_with_package).max_depth(max_depth).follow_links(lookup.follow_links).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "class") {
+            let file_name = path.file_stem()
+                .with_context(|| format!("无法获取文件名: {:?}", path))?;
+            
+            if matches_type_names(file_name, type_names, lookup.ignore_case) {
+                class_files.push(path.to_path_buf());
+            }
+        }
+    }
+    
+    Ok(class_files)
+}
+
+/// 判断不含扩展名的class文件名是否属于指定类型（主类或内部类）
+///
+/// 直接比较文件名的字节，不经过有损的UTF-8转换，非UTF-8文件名中不同的字节不会被当作相同的替换字符。
+/// `ignore_case`为true时按ASCII忽略大小写比较类名部分。
+fn matches_type_names(file_name: &OsStr, type_names: &[impl AsRef<OsStr>], ignore_case: bool) -> bool {
+    let file_name = file_name.as_encoded_bytes();
+    type_names.iter().any(|class_base_name| {
+        let class_base_name = class_base_name.as_ref().as_encoded_bytes();
+        let Some((head, rest)) = file_name.split_at_checked(class_base_name.len()) else {
+            return false;
+        };
+        let same_name = if ignore_case {
+            head.eq_ignore_ascii_case(class_base_name)
+        } else {
+            head == class_base_name
+        };
+        same_name && (rest.is_empty() || rest.starts_with(b"$"))
+    })
+}
+
+/// 判断不含扩展名的class文件名是否就是指定类型之一，而不是其内部类
+fn is_type_name(file_name: &OsStr, type_names: &[impl AsRef<OsStr>], ignore_case: bool) -> bool {
+    let file_name = file_name.as_encoded_bytes();
+    type_names.iter().any(|type_name| {
+        let type_name = type_name.as_ref().as_encoded_bytes();
+        if ignore_case {
+            file_name.eq_ignore_ascii_case(type_name)
+        } else {
+            file_name == type_name
+        }
+    })
+}
+
+/// 检查以忽略大小写的方式找到的class文件名与声明的类型名大小写是否一致，不一致时发出警告
+///
+/// 这类文件在大小写敏感的文件系统（如Linux）上部署后将无法按类名加载。
+fn warn_case_mismatch(java_rel_path: &Path, class_files: &[ClassLocation], type_names: &[OsString], warnings: &mut Vec<Warning>) {
+    for location in class_files {
+        let file_name = location.rel_path.file_stem().unwrap_or_default();
+        if !matches_type_names(file_name, type_names, false) {
+            warnings.push(Warning::new(WarningKind::CaseMismatch, Some(&location.rel_path),
+                format!("class文件名与源文件 {} 中声明的类型大小写不一致: {}",
+                    java_rel_path.to_string_lossy(),
+                    location.rel_path.to_string_lossy()
+                )));
+        }
+    }
+}
+
+/// 在多个class目录中按顺序查找Java文件中声明的类型对应的所有class文件
+///
+/// 同一个class文件存在于多个目录时按[`resolve_duplicate`]去重，结果记录在`duplicates`中。
+#[instrument(level = "debug", skip_all, fields(source = %java_rel_path.display()))]
+pub fn resolve_class_files(class_dirs: &[PathBuf], java_rel_path: &Path, type_names: &[impl AsRef<OsStr>], lookup: &LookupOptions, duplicates: &mut Duplicates, warnings: &mut Vec<Warning>) -> Result<Vec<ClassLocation>> {
+    let package_path = java_rel_path.parent().unwrap_or(Path::new(""));
+    let mut locations: Vec<ClassLocation> = Vec::new();
+    
+    for class_dir in class_dirs {
+        // 多版本目录按版本从高到低查找，最后查找基础目录
+        let mut roots = match lookup.multi_release {
+            Some(max_release) => multi_release_dirs(class_dir, max_release)?,
+            None => Vec::new(),
+        };
+        roots.push((None, class_dir.clone()));
+        let mut found_in_dir: HashSet<PathBuf> = HashSet::new();
+        
+        for (release, root) in roots {
+            for class_file in find_class_files_for_types(&root, package_path, type_names, lookup)? {
+                let rel_path = class_file.strip_prefix(&root)
+                    .with_context(|| format!("无法获取相对路径: {:?}", class_file))?
+                    .to_path_buf();
+                
+                // 同一class目录中已选择了更高版本的变体
+                if !found_in_dir.insert(rel_path.clone()) {
+                    continue;
+                }
+                let candidate = ClassLocation {
+                    class_dir: root.clone(),
+                    rel_path,
+                    release,
+                };
+                match locations.iter_mut().find(|location| location.rel_path == candidate.rel_path) {
+                    Some(existing) => {
+                        let existing_dir = existing.class_dir.clone();
+                        resolve_duplicate(existing, &existing_dir, candidate, &root, lookup.duplicate_policy, duplicates, warnings)?;
+                    }
+                    None => locations.push(candidate),
+                }
+            }
+        }
+    }
+    
+    Ok(locations)
+}
+
+/// 列出class目录中不高于`max_release`的多版本目录`META-INF/versions/N`，按版本从高到低排列
+fn multi_release_dirs(class_dir: &Path, max_release: u16) -> Result<Vec<(Option<u16>, PathBuf)>> {
+    let versions_dir = class_dir.join("META-INF").join("versions");
+    if !versions_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(&versions_dir).with_context(|| format!("无法读取目录: {:?}", versions_dir))? {
+        let entry = entry?;
+        let Some(release) = entry.file_name().to_str().and_then(|name| name.parse::<u16>().ok()) else {
+            continue;
+        };
+        if release <= max_release && entry.path().is_dir() {
+            dirs.push((Some(release), entry.path()));
+        }
+    }
+    dirs.sort_by_key(|(release, _)| std::cmp::Reverse(*release));
+    Ok(dirs)
+}
+
+/// 在class路径jar中按顺序查找Java文件中声明的类型对应的class文件，提取后追加到`locations`
+///
+/// 已存在于class目录或之前的jar中的class文件按[`resolve_duplicate`]去重。
+fn resolve_jar_class_files(jars: &[jar::ClassPathJar], java_rel_path: &Path, type_names: &[impl AsRef<OsStr>], lookup: &LookupOptions, locations: &mut Vec<ClassLocation>, duplicates: &mut Duplicates, warnings: &mut Vec<Warning>) -> Result<()> {
+    let package_path = java_rel_path.parent().unwrap_or(Path::new(""));
+    
+    for jar in jars {
+        let entries = jar.find_entries(package_path, |file_name| matches_type_names(OsStr::new(file_name), type_names, lookup.ignore_case));
+        for rel_path in jar.extract(&entries)? {
+            let candidate = ClassLocation {
+                release: None,
+                class_dir: jar.extract_dir.clone(),
+                rel_path,
+            };
+            match locations.iter_mut().find(|location| location.rel_path == candidate.rel_path) {
+                Some(existing) => {
+                    // 之前的jar中的文件显示jar路径，而不是临时提取目录
+                    let existing_source = jars.iter()
+                        .find(|jar| jar.extract_dir == existing.class_dir)
+                        .map_or(&existing.class_dir, |jar| &jar.path)
+                        .clone();
+                    resolve_duplicate(existing, &existing_source, candidate, &jar.path, lookup.duplicate_policy, duplicates, warnings)?;
+                }
+                None => locations.push(candidate),
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+/// 处理在多个位置找到的同一class文件，`existing`为先找到的文件，`existing_source`和`candidate_source`用于显示
+///
+/// 内容相同时保留`existing`并计为去重；内容不同时记录冲突，使用[`DuplicatePolicy::PreferLast`]时改用`candidate`。
+fn resolve_duplicate(existing: &mut ClassLocation, existing_source: &Path, candidate: ClassLocation, candidate_source: &Path, policy: DuplicatePolicy, duplicates: &mut Duplicates, warnings: &mut Vec<Warning>) -> Result<()> {
+    let rel_path = candidate.rel_path.clone();
+    if file_sha256(&existing.path())? == file_sha256(&candidate.path())? {
+        info!("class文件 {} 同时存在于 {:?} 和 {:?}，内容相同，只复制一次",
+            rel_path.to_string_lossy(), existing_source, candidate_source);
+        duplicates.deduped += 1;
+        return Ok(());
+    }
+    
+    duplicates.conflicts.push(rel_path.clone());
+    let (used, ignored) = match policy {
+        DuplicatePolicy::Error => return Ok(()),
+        DuplicatePolicy::PreferFirst => (existing_source, candidate_source),
+        DuplicatePolicy::PreferLast => (candidate_source, existing_source),
+    };
+    warnings.push(Warning::new(WarningKind::Shadowed, Some(&rel_path),
+        format!("class文件 {} 同时存在于多个位置且内容不同，使用 {:?}，忽略 {:?}",
+            rel_path.to_string_lossy(),
+            used,
+            ignored
+        )));
+    if policy == DuplicatePolicy::PreferLast {
+        *existing = candidate;
+    }
+    Ok(())
+}
+
+/// 查找class目录中的资源文件（非class文件），`skip`中的相对路径已从源代码目录复制，不再重复
+///
+/// 同一个资源文件存在于多个class目录时使用第一个目录中的文件。
+fn collect_class_resources(class_dirs: &[PathBuf], skip: &HashSet<&Path>, lookup: &LookupOptions, warnings: &mut Vec<Warning>) -> Result<Vec<ClassLocation>> {
+    let mut resources: Vec<ClassLocation> = Vec::new();
+    
+    for class_dir in class_dirs {
+        for entry in WalkDir::new(class_dir).follow_links(lookup.follow_links).sort_by_file_name() {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.is_file() || path.extension().is_some_and(|ext| ext == "class") {
+                continue;
+            }
+            
+            let rel_path = path.strip_prefix(class_dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", path))?;
+            if skip.contains(rel_path) {
+                continue;
+            }
+            if let Some(existing) = resources.iter().find(|location| location.rel_path == rel_path) {
+                warnings.push(Warning::new(WarningKind::Shadowed, Some(rel_path),
+                    format!("资源文件 {} 同时存在于多个目录，使用 {:?}，忽略 {:?}",
+                        rel_path.to_string_lossy(),
+                        existing.class_dir,
+                        class_dir
+                    )));
+                continue;
+            }
+            
+            resources.push(ClassLocation {
+                release: None,
+                class_dir: class_dir.clone(),
+                rel_path: rel_path.to_path_buf(),
+            });
+        }
+    }
+    
+    Ok(resources)
+}
+
+/// 查找class目录中不属于任何源文件的class文件，返回相对于class目录的路径
+///
+/// 主类已有对应源文件的内部类（`Foo$Bar.class`）不算作孤立文件。
+fn find_orphan_classes(class_dirs: &[PathBuf], source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, lookup: &LookupOptions) -> Result<Vec<PathBuf>> {
+    let resolved: HashSet<&Path> = source_to_classes.values()
+        .flatten()
+        .map(|location| location.rel_path.as_path())
+        .collect();
+    let mut orphans = BTreeSet::new();
+    
+    for class_dir in class_dirs {
+        for entry in WalkDir::new(class_dir).follow_links(lookup.follow_links).sort_by_file_name() {
+            let entry = entry?;
+            let path = entry.path();
+            if !path.is_file() || path.extension().is_none_or(|ext| ext != "class") {
+                continue;
+            }
+            
+            let mut rel_path = path.strip_prefix(class_dir)
+                .with_context(|| format!("无法获取相对路径: {:?}", path))?;
+            // 多版本目录中的class文件按去掉版本目录后的路径判断
+            if lookup.multi_release.is_some() {
+                if let Some(versioned_path) = strip_release_prefix(rel_path) {
+                    rel_path = versioned_path;
+                }
+            }
+            if resolved.contains(rel_path) {
+                continue;
+            }
+            
+            // 内部类的主类已有对应源文件
+            let file_name = rel_path.file_name().unwrap_or_default().to_string_lossy();
+            if let Some((base_name, _)) = file_name.split_once('$') {
+                if resolved.contains(rel_path.with_file_name(format!("{}.class", base_name)).as_path()) {
+                    continue;
+                }
+            }
+            
+            orphans.insert(rel_path.to_path_buf());
+        }
+    }
+    
+    Ok(orphans.into_iter().collect())
+}
+
+/// 检查已复制的class文件所在的包目录，主类不在选择的源文件中的内部类（`Foo$Bar.class`）按主类各产生一条警告
+///
+/// 这些内部类通常说明`--include`等过滤条件排除了已复制的类所依赖的类，只检查class目录，不检查jar和多版本目录。
+fn warn_missing_dependencies(class_dirs: &[PathBuf], source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, warnings: &mut Vec<Warning>) {
+    let locations = source_to_classes.values()
+        .flatten()
+        .filter(|location| location.release.is_none());
+    let resolved: HashSet<&Path> = locations.clone()
+        .map(|location| location.rel_path.as_path())
+        .collect();
+    let packages: BTreeSet<&Path> = locations
+        .map(|location| location.rel_path.parent().unwrap_or(Path::new("")))
+        .collect();
+    
+    let mut inner_classes: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+    for class_dir in class_dirs.iter().filter(|dir| dir.is_dir()) {
+        for package in &packages {
+            let Ok(entries) = fs::read_dir(class_dir.join(package)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let file_name = entry.file_name().to_string_lossy().into_owned();
+                let Some((base_name, _)) = file_name.strip_suffix(".class").and_then(|stem| stem.split_once('$')) else {
+                    continue;
+                };
+                let base_path = package.join(format!("{}.class", base_name));
+                if resolved.contains(base_path.as_path()) || resolved.contains(package.join(&file_name).as_path()) {
+                    continue;
+                }
+                inner_classes.entry(base_path).or_default().insert(file_name);
+            }
+        }
+    }
+    
+    for (base_path, names) in inner_classes {
+        let names: Vec<String> = names.into_iter().collect();
+        warnings.push(Warning::new(WarningKind::MissingDependency, Some(&base_path),
+            format!("已复制的包中有 {} 个内部类（{}）的主类 {} 不在选择的源文件中，可能被过滤条件排除，复制结果可能不完整",
+                names.len(), names.join(", "), base_path.to_string_lossy())));
+    }
+}
+
+/// 去掉多版本目录前缀`META-INF/versions/N/`，不在多版本目录中时返回None
+fn strip_release_prefix(rel_path: &Path) -> Option<&Path> {
+    let mut components = rel_path.components();
+    let prefix: Vec<_> = components.by_ref().take(3).map(|component| component.as_os_str()).collect();
+    let is_versioned = prefix.len() == 3
+        && prefix[0] == "META-INF"
+        && prefix[1] == "versions"
+        && prefix[2].to_str().is_some_and(|release| release.parse::<u16>().is_ok());
+    is_versioned.then_some(components.as_path())
+}
+
+/// 检查源文件对应的class文件是否都位于期望的包目录（`lookup_rel_path`所在的目录）下，不在时发出警告
+///
+/// class文件分散在多个包目录中通常说明输出目录中残留了过期的构建产物。
+fn warn_divergent_packages(java_rel_path: &Path, lookup_rel_path: &Path, class_files: &[ClassLocation], warnings: &mut Vec<Warning>) {
+    let expected = lookup_rel_path.parent().unwrap_or(Path::new(""));
+    let divergent: Vec<_> = class_files.iter()
+        .filter(|location| location.rel_path.parent().unwrap_or(Path::new("")) != expected)
+        .map(|location| location.rel_path.to_string_lossy())
+        .collect();
+    
+    if !divergent.is_empty() {
+        warnings.push(Warning::new(WarningKind::DivergentPackage, Some(java_rel_path),
+            format!("源文件 {} 的部分class文件不在期望的包目录 {:?} 下: {}",
+                java_rel_path.to_string_lossy(),
+                expected,
+                divergent.join(", ")
+            )));
+    }
+}
+
+/// 检查同一个简单类名是否解析到了多个目录中的class文件，是时发出警告
+///
+/// 内部类按其顶层类名归类，`package-info`和`module-info`不参与检查。不同包中的同名类是合法的，
+/// 但平铺的class目录或残留的旧文件也会造成这种情况，因此只警告不报错。
+fn warn_duplicate_simple_names(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, warnings: &mut Vec<Warning>) {
+    let mut directories: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+    for location in source_to_classes.values().flatten() {
+        let path = location.path();
+        let Some(file_stem) = path.file_stem().map(|stem| stem.to_string_lossy()) else {
+            continue;
+        };
+        let simple_name = file_stem.split('$').next().unwrap_or_default();
+        if simple_name == "package-info" || simple_name == "module-info" {
+            continue;
+        }
+        directories.entry(simple_name.to_string())
+            .or_default()
+            .insert(path.parent().unwrap_or(Path::new("")).to_path_buf());
+    }
+    
+    for (simple_name, dirs) in directories.iter().filter(|(_, dirs)| dirs.len() > 1) {
+        let dirs: Vec<_> = dirs.iter().map(|dir| dir.to_string_lossy()).collect();
+        warnings.push(Warning::new(WarningKind::DuplicateSimpleName, None,
+            format!("类名 {} 对应多个目录中的class文件: {}，可能存在过期的构建产物", simple_name, dirs.join(", "))));
+    }
+}
+
+/// 检查平铺输出时是否有多个class文件使用相同的文件名
+///
+/// 存在冲突时，除非指定了`force`否则返回错误；指定`force`时只发出警告，后复制的文件覆盖先复制的文件。
+fn check_flat_collisions(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, class_renamer: &ClassRenamer, force: bool, warnings: &mut Vec<Warning>) -> Result<()> {
+    let mut by_name: BTreeMap<OsString, BTreeSet<&Path>> = BTreeMap::new();
+    for location in source_to_classes.values().flatten() {
+        if let Some(file_name) = location.rel_path.file_name() {
+            by_name.entry(class_renamer.rename_file_name(file_name)).or_default().insert(&location.rel_path);
+        }
+    }
+    
+    let mut collisions = 0;
+    for (file_name, rel_paths) in by_name.iter().filter(|(_, rel_paths)| rel_paths.len() > 1) {
+        let rel_paths: Vec<_> = rel_paths.iter().map(|path| path.to_string_lossy()).collect();
+        let message = format!("平铺输出中的文件名冲突: {}（{}）", file_name.to_string_lossy(), rel_paths.join(", "));
+        if force {
+            warnings.push(Warning::new(WarningKind::NameCollision, Some(Path::new(file_name.as_os_str())), message));
+        } else {
+            error!("{}", message);
+        }
+        collisions += 1;
+    }
+    
+    if collisions > 0 && !force {
+        bail!("{} 个class文件名在平铺输出中冲突，使用--force允许覆盖", collisions);
+    }
+    Ok(())
+}
+
+/// class文件在输出目录中的相对路径（不含`--bucket-by-version`的版本目录）
+fn class_target_rel_path(location: &ClassLocation, options: &Options, class_prefixes: &HashMap<PathBuf, PathBuf>, package_mapper: &PackageMapper, class_renamer: &ClassRenamer) -> PathBuf {
+    let rel_path = location.rel_path.as_path();
+    match options.output_layout {
+        OutputLayout::Package => {
+            let target = package_mapper.map(&class_renamer.rename(rel_path));
+            match class_prefixes.get(location.root_class_dir()) {
+                Some(prefix) => prefix.join(target),
+                None => target,
+            }
+        }
+        OutputLayout::Flat => PathBuf::from(class_renamer.rename_file_name(rel_path.file_name().unwrap_or_default())),
+    }
+}
+
+/// 检查是否有目标路径（相对于输出目录）只有大小写不同，存在时逐组列出并返回错误
+///
+/// 完全相同的目标路径不在此检查，由平铺输出和重命名的冲突检查处理。
+fn check_case_collisions(targets: impl Iterator<Item = PathBuf>) -> Result<()> {
+    let mut by_folded: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+    for target in targets {
+        by_folded.entry(target.to_string_lossy().to_lowercase()).or_default().insert(target);
+    }
+    
+    let mut collisions = 0;
+    for targets in by_folded.values().filter(|targets| targets.len() > 1) {
+        let targets: Vec<_> = targets.iter().map(|path| path.to_string_lossy()).collect();
+        error!("目标路径只有大小写不同: {}", targets.join(", "));
+        collisions += 1;
+    }
+    
+    if collisions > 0 {
+        bail!("{} 组目标路径只有大小写不同，复制到不区分大小写的文件系统时会互相覆盖", collisions);
+    }
+    Ok(())
+}
+
+/// 检查重命名后是否有多个class文件使用相同的目标路径，存在时发出警告，后复制的文件覆盖先复制的文件
+fn warn_rename_collisions(source_to_classes: &BTreeMap<PathBuf, Vec<ClassLocation>>, class_renamer: &ClassRenamer, warnings: &mut Vec<Warning>) {
+    let mut by_target: BTreeMap<PathBuf, BTreeSet<&Path>> = BTreeMap::new();
+    for location in source_to_classes.values().flatten() {
+        by_target.entry(class_renamer.rename(&location.rel_path)).or_default().insert(&location.rel_path);
+    }
+    
+    for (target, rel_paths) in by_target.iter().filter(|(_, rel_paths)| rel_paths.len() > 1) {
+        let rel_paths: Vec<_> = rel_paths.iter().map(|path| path.to_string_lossy()).collect();
+        warnings.push(Warning::new(WarningKind::NameCollision, Some(target),
+            format!("重命名后多个class文件的目标路径相同: {}（{}）", target.to_string_lossy(), rel_paths.join(", "))));
+    }
+}
+
+/// 模块描述的文件名（不含扩展名），位于源代码目录和class目录的根目录
+const MODULE_INFO: &str = "module-info";
+/// 包描述的文件名（不含扩展名）
+const PACKAGE_INFO: &str = "package-info";
+
+/// 确定源文件中声明的顶层类型名，扫描不到任何声明时退回使用文件名
+///
+/// Kotlin文件额外包含`<文件名>Kt`，即编译器存放顶层函数和属性的类。
+///
+/// 退回使用的文件名保留原始的`OsStr`，非UTF-8的文件名也能按字节匹配class文件。
+fn source_type_names(java_file: &Path) -> Result<Vec<OsString>> {
+    let java_file_name = java_file.file_stem()
+        .with_context(|| format!("无法获取文件名: {:?}", java_file))?;
+    
+    // module-info.java和package-info.java不声明类型，只对应同名的class文件
+    if [MODULE_INFO, PACKAGE_INFO].iter().any(|name| java_file_name == *name) {
+        return Ok(vec![java_file_name.to_os_string()]);
+    }
+    
+    let mut type_names: Vec<OsString> = read_top_level_types(java_file)?.into_iter()
+        .map(OsString::from)
+        .collect();
+    if type_names.is_empty() {
+        type_names.push(java_file_name.to_os_string());
+    }
+    if java_file.extension().is_some_and(|ext| ext == "kt") {
+        let mut facade_name = java_file_name.to_os_string();
+        facade_name.push("Kt");
+        if !type_names.contains(&facade_name) {
+            type_names.push(facade_name);
+        }
+    }
+    Ok(type_names)
+}
+
+/// 读取class文件的版本信息
+pub fn read_class_file_version(path: &Path) -> Result<JavaClassVersion> {
+    // 打开文件
+    let file = fs::File::open(path)
+        .with_context(|| format!("无法打开class文件: {:?}", path))?;
+    
+    // 读取前8个字节
+    let mut header = Vec::with_capacity(8);
+    file.take(8).read_to_end(&mut header)
+        .with_context(|| format!("无法读取class文件头: {:?}", path))?;
+    parse_class_version(path, &header)
+}
+
+/// 解析class文件的前8个字节，校验魔数和主版本号
+fn parse_class_version(path: &Path, header: &[u8]) -> Result<JavaClassVersion> {
+    let truncated = || Error::MalformedClass(format!("class文件被截断，长度不足8字节: {:?} (实际 {} 字节)", path, header.len()));
+    let mut reader = classfile::ClassFileReader::new(header);
+    
+    // 检查魔数 (0xCAFEBABE)
+    let magic = reader.read_u32().map_err(|_| truncated())?;
+    if magic != 0xCAFEBABE {
+        return Err(Error::MalformedClass(format!("无效的class文件格式，魔数不匹配（{}）: {:?}", classfile::magic_mismatch_hint(header), path)).into());
+    }
+    
+    // 读取次版本号和主版本号
+    let minor = reader.read_u16().map_err(|_| truncated())?;
+    let major = reader.read_u16().map_err(|_| truncated())?;
+    
+    // JDK 1.1（主版本号45）之前没有合法的class文件版本
+    if major < 45 {
+        return Err(Error::MalformedClass(format!("无效的class文件版本，主版本号 {} 低于45: {:?}", major, path)).into());
+    }
+    
+    Ok(JavaClassVersion { major, minor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jdk_version_for_newer_majors() {
+        let version = JavaClassVersion { major: 67, minor: 0 };
+        assert_eq!(version.to_jdk_version(), "JDK 23");
+    }
+
+    #[test]
+    fn major_below_45_is_malformed() {
+        let header = [0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 0];
+        let err = parse_class_version(Path::new("Foo.class"), &header).unwrap_err();
+        assert!(matches!(err.downcast_ref::<Error>(), Some(Error::MalformedClass(_))));
+    }
+
+    #[test]
+    fn truncated_header_is_malformed() {
+        let err = parse_class_version(Path::new("Foo.class"), &[0xCA, 0xFE, 0xBA, 0xBE, 0]).unwrap_err();
+        assert!(matches!(err.downcast_ref::<Error>(), Some(Error::MalformedClass(message)) if message.contains("被截断")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn matches_non_utf8_class_names_by_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("com").join("example");
+        fs::create_dir_all(&package).unwrap();
+        // Caf\xE9与Caf\xE8经过有损转换后都是"Caf\u{FFFD}"
+        for name in [&b"Caf\xE9.class"[..], b"Caf\xE9$Inner.class", b"Caf\xE8.class", b"Caf\xE8$Inner.class"] {
+            fs::write(package.join(OsStr::from_bytes(name)), b"").unwrap();
+        }
+        
+        let java_rel_path = Path::new("com/example").join(OsStr::from_bytes(b"Caf\xE9.java"));
+        let found = find_class_files(dir.path(), &java_rel_path, &LookupOptions::default()).unwrap();
+        let names: Vec<&[u8]> = found.iter()
+            .map(|path| path.file_name().unwrap().as_bytes())
+            .collect();
+        assert_eq!(names, [&b"Caf\xE9$Inner.class"[..], b"Caf\xE9.class"]);
+    }
+
+    #[test]
+    fn buffered_copy_matches_fs_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.bin");
+        // 长度不是缓冲区大小的整数倍，最后一块不满
+        let content: Vec<u8> = (0..100_003u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect();
+        fs::write(&source, &content).unwrap();
+        
+        let expected = dir.path().join("expected.bin");
+        fs::copy(&source, &expected).unwrap();
+        for buffer_size in [1, 7, 4096, 65536, 1 << 20] {
+            let target = dir.path().join(format!("buffered-{}.bin", buffer_size));
+            assert_eq!(buffered_copy(&source, &target, buffer_size).unwrap(), content.len() as u64);
+            assert_eq!(fs::read(&target).unwrap(), fs::read(&expected).unwrap(), "缓冲区大小 {}", buffer_size);
+        }
+        
+        // 覆盖已有的较长文件时截断多余内容
+        let target = dir.path().join("existing.bin");
+        fs::write(&target, vec![0xFF; content.len() * 2]).unwrap();
+        buffered_copy(&source, &target, 4096).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), content);
+        
+        let empty = dir.path().join("empty.bin");
+        fs::write(&empty, b"").unwrap();
+        assert_eq!(buffered_copy(&empty, &dir.path().join("empty-copy.bin"), 16).unwrap(), 0);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn buffered_copy_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("run.sh");
+        fs::write(&source, b"#!/bin/sh\n").unwrap();
+        fs::set_permissions(&source, fs::Permissions::from_mode(0o750)).unwrap();
+        let target = dir.path().join("copy.sh");
+        buffered_copy(&source, &target, 8).unwrap();
+        assert_eq!(target.metadata().unwrap().permissions().mode() & 0o777, 0o750);
+    }
+
+    #[test]
+    fn rewriting_hardlinked_output_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_dir = dir.path().join("src");
+        let class_dir = dir.path().join("classes");
+        let output_dir = dir.path().join("out");
+        fs::create_dir_all(source_dir.join("com")).unwrap();
+        fs::create_dir_all(&class_dir).unwrap();
+        let original = source_dir.join("com").join("a.properties");
+        fs::write(&original, b"a=1\r\nb=2\r\n").unwrap();
+        let class_dirs = [class_dir];
+        
+        let hardlink = Options { hardlink: true, quiet: true, ..Options::default() };
+        run(&source_dir, &class_dirs, &output_dir, &hardlink).unwrap();
+        
+        let normalize = Options { force: true, normalize_eol: Some(LineEnding::Lf), quiet: true, ..Options::default() };
+        run(&source_dir, &class_dirs, &output_dir, &normalize).unwrap();
+        assert_eq!(fs::read(output_dir.join("com").join("a.properties")).unwrap(), b"a=1\nb=2\n");
+        assert_eq!(fs::read(&original).unwrap(), b"a=1\r\nb=2\r\n");
+    }
+
+    /// 超过MAX_PATH的深层包目录
+    fn deep_package_path(root: &str) -> String {
+        let mut path = root.to_string();
+        while path.len() <= MAX_PATH {
+            path.push_str("com\\example\\enterprise\\");
+        }
+        path + "Foo.class"
+    }
+
+    #[test]
+    fn extended_length_prefix_for_deep_paths() {
+        let path = deep_package_path(r"C:\build\out\");
+        assert_eq!(extended_length_path(&path), format!(r"\\?\{}", path));
+        assert_eq!(extended_length_path(&extended_length_path(&path)), format!(r"\\?\{}", path));
+
+        let unc = deep_package_path(r"\\server\share\out\");
+        assert_eq!(extended_length_path(&unc), format!(r"\\?\UNC\{}", &unc[2..]));
+        assert_eq!(extended_length_path("C:/build/out/Foo.class"), r"\\?\C:\build\out\Foo.class");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn copies_into_deep_windows_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = format!("{}\\", dir.path().display());
+        let target = long_path(PathBuf::from(deep_package_path(&root)));
+        assert!(target.to_string_lossy().starts_with(r"\\?\"));
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&target, b"class").unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"class");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn long_paths_unchanged_off_windows() {
+        let path = PathBuf::from(format!("/{}", "com/example/enterprise/".repeat(20)));
+        assert_eq!(long_path(path.clone()), path);
+    }
+}
@@ -1,309 +1,743 @@
 use std::path::{Path, PathBuf};
+use std::ffi::{OsStr, OsString};
 use std::fs;
-use std::io::Read;
-use std::collections::HashMap;
+use std::process;
+use std::time::SystemTime;
 use structopt::StructOpt;
-use walkdir::WalkDir;
-use anyhow::{Result, Context, bail};
-use log::{info, error, warn};
+use structopt::clap::{AppSettings, Shell};
+use anyhow::{Result, Context};
+use serde::Deserialize;
+use owo_colors::OwoColorize;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use src_to_class::{DuplicatePolicy, LineEnding, Options, OutputLayout};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "src_to_class", about = "将Java源文件对应的class文件复制到指定目录")]
 struct Opt {
-    /// 源代码路径文件夹，包含.java文件
-    #[structopt(short, long, parse(from_os_str))]
-    source_dir: PathBuf,
+    /// 源代码路径文件夹，包含.java文件，可以引用环境变量（$VAR或${VAR}）
+    #[structopt(short, long, parse(try_from_os_str = expand_path))]
+    source_dir: Option<PathBuf>,
 
-    /// 编译后的class文件夹
-    #[structopt(short, long, parse(from_os_str))]
-    class_dir: PathBuf,
+    /// 编译后的class文件夹，可重复指定，按顺序查找，可以引用环境变量（$VAR或${VAR}）
+    #[structopt(short, long, parse(try_from_os_str = expand_path), number_of_values = 1)]
+    class_dir: Vec<PathBuf>,
 
-    /// 输出目录
-    #[structopt(short, long, parse(from_os_str))]
-    output_dir: PathBuf,
+    /// 在class目录之后查找class文件的jar/zip文件，可重复指定，按顺序查找
+    #[structopt(long = "classpath-jar", parse(from_os_str), number_of_values = 1)]
+    classpath_jars: Vec<PathBuf>,
+
+    /// 输出目录，可以引用环境变量（$VAR或${VAR}）
+    #[structopt(short, long, parse(try_from_os_str = expand_path))]
+    output_dir: Option<PathBuf>,
+
+    /// class文件在输出目录中的路径相对于该目录（各class目录的上级目录）计算，默认相对于各class目录
+    #[structopt(long, parse(from_os_str), conflicts_with = "classpath-jars")]
+    class_relative_to: Option<PathBuf>,
+
+    /// 除.java外也查找class文件的源文件扩展名（如 kt、scala），可重复指定
+    #[structopt(long = "source-ext", number_of_values = 1)]
+    source_exts: Vec<String>,
+
+    /// 允许写入非空的输出目录（默认输出目录非空时报错），平铺输出时允许同名class文件互相覆盖
+    #[structopt(long)]
+    force: bool,
+
+    /// 写入前清空输出目录中的已有内容（需配合--force使用）
+    #[structopt(long, requires = "force")]
+    clean: bool,
+
+    /// TOML配置文件，提供上述路径及include/exclude的默认值，命令行参数优先
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// 项目根目录，按Maven（src/main/java、target/classes）或Gradle（src/main/java、build/classes/java/main）的约定确定源代码目录、class目录和输出目录，显式指定的参数优先
+    #[structopt(long, parse(try_from_os_str = expand_path))]
+    project_root: Option<PathBuf>,
+
+    /// 仅打印将要执行的复制操作，不实际创建目录或复制文件
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// 只列出每个源文件对应的class文件，不读取class文件版本，也不复制文件
+    #[structopt(long, conflicts_with_all = &["dry-run", "watch", "summary-json"])]
+    list_only: bool,
+
+    /// 先复制到输出目录旁的临时目录，全部完成后再整体替换输出目录，其他进程不会读到复制了一半的结果
+    #[structopt(long, conflicts_with_all = &["clean", "no-output-dir", "watch"])]
+    atomic: bool,
+
+    /// 只并行读取所有class文件的JDK版本并打印统计，不复制文件（线程数由--read-jobs指定）
+    #[structopt(long, conflicts_with_all = &["dry-run", "list-only", "watch"])]
+    audit: bool,
+
+    /// 只检查每个源文件都能找到class文件、所有class文件的版本不超过--target-jdk，不需要输出目录，也不复制文件
+    #[structopt(long, conflicts_with_all = &["dry-run", "list-only", "audit", "watch"])]
+    verify_only: bool,
+
+    /// 将复制结果以JSON格式写入指定文件
+    #[structopt(long, parse(from_os_str))]
+    report: Option<PathBuf>,
+
+    /// 每复制一个文件就向指定文件写入一行JSON（JSON Lines），适用于文件数量很多的运行
+    #[structopt(long, parse(from_os_str))]
+    report_jsonl: Option<PathBuf>,
+
+    /// 将复制过程中的警告以JSON格式写入指定文件（默认按类型分组打印到标准错误输出）
+    #[structopt(long, parse(from_os_str))]
+    warnings_file: Option<PathBuf>,
+
+    /// 将源文件到class文件的对应关系以文本格式写入指定文件
+    #[structopt(long, parse(from_os_str))]
+    manifest: Option<PathBuf>,
+
+    /// 将每个复制的文件的路径、大小、SHA-256和JDK版本以CSV格式写入指定文件（文件清册），供合规工具读取
+    #[structopt(long, parse(from_os_str))]
+    inventory: Option<PathBuf>,
+
+    /// 期望的输出文件列表（每行一个相对路径），复制完成后与输出目录比较，有缺少或多出的文件时报错退出
+    #[structopt(long, parse(from_os_str))]
+    expect: Option<PathBuf>,
+
+    /// 并行处理的线程数，默认使用CPU核心数；作为--read-jobs和--copy-jobs的默认值
+    #[structopt(long)]
+    jobs: Option<usize>,
+
+    /// 并行读取class文件JDK版本的线程数，默认同--jobs
+    #[structopt(long)]
+    read_jobs: Option<usize>,
+
+    /// 并行复制文件的线程数，默认同--jobs
+    #[structopt(long)]
+    copy_jobs: Option<usize>,
+
+    /// 允许的最高JDK版本（如 8），任何class文件版本高于该版本时操作失败
+    #[structopt(long)]
+    target_jdk: Option<u16>,
+
+    /// 检测到多个不同的JDK版本时报错退出，而不只是警告
+    #[structopt(long)]
+    fail_on_mixed_versions: bool,
+
+    /// 只复制指定JDK版本（如 8）编译的class文件，其他版本的class文件跳过
+    #[structopt(long)]
+    only_jdk: Option<u16>,
+
+    /// 从文件读取源文件列表（每行一个路径，-表示标准输入），代替遍历源代码目录
+    #[structopt(long, parse(from_os_str))]
+    source_list: Option<PathBuf>,
+
+    /// 从JSON文件读取源文件到class文件的对应关系（[{"source": ..., "classes": [...]}]），不再遍历源代码目录或查找class文件
+    #[structopt(long, parse(from_os_str), conflicts_with_all = &["source-list", "watch"])]
+    mapping: Option<PathBuf>,
+
+    /// 只处理匹配该glob模式的源文件（相对于源代码目录），可重复指定
+    #[structopt(long = "include")]
+    includes: Vec<String>,
+
+    /// 排除匹配该glob模式的源文件（相对于源代码目录），可重复指定，优先于--include
+    #[structopt(long = "exclude")]
+    excludes: Vec<String>,
+
+    /// 复制文本资源文件（非Java文件和class目录资源文件）时统一转换为该换行符，二进制文件原样复制
+    #[structopt(long, possible_values = &["lf", "crlf"])]
+    normalize_eol: Option<LineEnding>,
+
+    /// --normalize-eol处理的文本文件扩展名，可重复指定，指定后替换默认列表
+    #[structopt(long = "text-ext", number_of_values = 1, requires = "normalize-eol")]
+    text_exts: Vec<String>,
+
+    /// class文件的输出布局：package保留包目录结构，flat将所有class文件直接放在输出目录下
+    #[structopt(long, default_value = "package", possible_values = &["package", "flat"])]
+    output_layout: OutputLayout,
+
+    /// 按JDK版本分目录输出class文件（如 jdk8/com/example/Foo.class），非Java文件放在resources/下
+    #[structopt(long, conflicts_with = "class-relative-to")]
+    bucket_by_version: bool,
+
+    /// 复制时将包目录重定位到另一个包（如 com.old=com.new），只改变输出路径，不修改class文件内容，可重复指定
+    #[structopt(long = "map-package", number_of_values = 1)]
+    map_packages: Vec<String>,
+
+    /// 按正则表达式重命名class文件（如 ^Foo$=FooImpl），作用于外部类名，内部类一并重命名，可重复指定
+    #[structopt(long = "rename", number_of_values = 1)]
+    renames: Vec<String>,
+
+    /// 跳过带有ACC_SYNTHETIC标志的编译器生成类（如enum switch生成的映射类）
+    #[structopt(long)]
+    skip_synthetic: bool,
+
+    /// 只复制与源文件中声明的类型同名的class文件（如Foo.class），不复制Foo$Bar.class等内部类和匿名类
+    #[structopt(long)]
+    no_inner_classes: bool,
+
+    /// 根据class文件版本、预览标志和SourceFile属性推测每个class文件的编译器（如javac 17），在详细输出和汇总中列出，仅供排查
+    #[structopt(long)]
+    guess_compiler: bool,
+
+    /// 在输出目录中为class文件和非Java文件创建硬链接代替复制（需要在同一文件系统上），无法创建时改为复制并发出警告
+    #[structopt(long)]
+    hardlink: bool,
+
+    /// 在输出目录中创建指向原文件的符号链接代替复制，重新编译后立即生效，适合本地反复运行；输出不能脱离原目录单独使用
+    #[structopt(long, conflicts_with_all = &["hardlink", "strip-debug", "normalize-eol"])]
+    symlink: bool,
+
+    /// 复制后保留源文件的修改时间
+    #[structopt(long)]
+    preserve_timestamps: bool,
+
+    /// 复制class文件时去除行号表、局部变量表和源文件名等调试信息
+    #[structopt(long)]
+    strip_debug: bool,
+
+    /// 计算每个复制文件的SHA-256并写入报告，汇总中显示所有class文件的组合SHA-256
+    #[structopt(long)]
+    checksums: bool,
+
+    /// 始终打印每个文件的详细信息（输出到终端时默认只显示进度条）
+    #[structopt(short, long)]
+    verbose: bool,
+
+    /// 不打印每个文件的详细信息和分隔线，只保留汇总信息、错误和警告
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// 文件大小显示为精确的字节数（默认显示为KiB/MiB）
+    #[structopt(long)]
+    bytes: bool,
+
+    /// 复制完成后以树形结构打印输出目录，标注文件大小和class文件的JDK版本
+    #[structopt(long, conflicts_with = "summary-json")]
+    tree: bool,
+
+    /// 以单行JSON将汇总信息输出到标准输出，代替文本汇总，同时隐含--quiet
+    #[structopt(long, conflicts_with = "verbose")]
+    summary_json: bool,
+
+    /// 在汇总信息后打印收集、解析、复制各阶段的耗时
+    #[structopt(long, conflicts_with = "summary-json")]
+    timings: bool,
+
+    /// 在汇总信息后按包的前几级（见--group-depth）汇总复制的class文件数和总大小
+    #[structopt(long, conflicts_with = "summary-json")]
+    group_report: bool,
+
+    /// --group-report汇总时使用的包名级数
+    #[structopt(long, default_value = "2")]
+    group_depth: usize,
+
+    /// 在汇总信息后以条形图显示各JDK版本的class文件数
+    #[structopt(long, conflicts_with = "summary-json")]
+    histogram: bool,
+
+    /// 复制完成后将输出目录打包为jar文件
+    #[structopt(long, parse(from_os_str))]
+    jar: Option<PathBuf>,
+
+    /// 复制完成后按包名的前几级（见--jar-package-depth）分组，在输出目录中为每组class文件生成一个jar（如com.example.jar）
+    #[structopt(long, conflicts_with_all = &["no-output-dir", "bucket-by-version"])]
+    jar_per_package: bool,
+
+    /// --jar-per-package分组时使用的包名级数
+    #[structopt(long, default_value = "1")]
+    jar_package_depth: usize,
+
+    /// 复制完成后用输出目录中的文件更新已有的jar文件：替换内容变化的条目、新增条目，保留其他条目
+    #[structopt(long, parse(from_os_str))]
+    update_jar: Option<PathBuf>,
+
+    /// 只生成或更新jar文件，不在输出目录中保留复制的文件（需配合--jar或--update-jar使用）
+    #[structopt(long)]
+    no_output_dir: bool,
+
+    /// 通过比较文件内容的SHA-256判断目标文件是否未变化（默认比较大小和修改时间）
+    #[structopt(long)]
+    verify_hash: bool,
+
+    /// 复制文件遇到暂时性IO错误（如网络存储超时）时的最大重试次数
+    #[structopt(long, default_value = "0")]
+    copy_retries: u32,
+
+    /// 第一次重试前等待的毫秒数，之后每次重试加倍
+    #[structopt(long, default_value = "200")]
+    retry_delay_ms: u64,
+
+    /// 复制文件时使用该大小（字节）的缓冲区逐块读写，代替系统默认的复制方式，用于在网络文件系统上调优
+    #[structopt(long)]
+    buffer_size: Option<usize>,
+
+    /// class文件版本的缓存文件，按路径、大小和修改时间缓存版本号，加快重复运行
+    #[structopt(long, parse(from_os_str))]
+    version_cache: Option<PathBuf>,
+
+    /// 按多版本jar的规则查找class目录的META-INF/versions/N，优先使用不高于该版本的最高版本class文件，没有时使用基础class文件
+    #[structopt(long)]
+    multi_release: Option<u16>,
+
+    /// 同一class文件存在于多个class目录或jar且内容不同时，使用先找到的文件
+    #[structopt(long, conflicts_with = "prefer-last")]
+    prefer_first: bool,
+
+    /// 同一class文件存在于多个class目录或jar且内容不同时，使用后找到的文件
+    #[structopt(long)]
+    prefer_last: bool,
+
+    /// 跳过格式无效的class文件（如魔数不匹配的同名文件）并单独计数，而不是中止操作
+    #[structopt(long)]
+    skip_invalid_class: bool,
+
+    /// 只复制指定的类（全限定名，如 com.example.Foo）及其内部类的class文件，可重复指定，不复制非Java文件
+    #[structopt(long = "class", number_of_values = 1)]
+    classes: Vec<String>,
+
+    /// 按源文件中的package声明确定在class目录中查找的包目录，用于源文件没有按包目录存放的项目
+    #[structopt(long)]
+    derive_package_from_source: bool,
+
+    /// 匹配class文件名时忽略大小写（用于macOS、Windows等大小写不敏感的文件系统），大小写不一致时发出警告
+    #[structopt(long)]
+    ignore_case: bool,
+
+    /// 遍历源代码目录和class目录时跟随符号链接
+    #[structopt(long)]
+    follow_symlinks: bool,
+
+    /// 同时复制class目录中没有对应源文件的资源文件（如.properties、META-INF/services下的文件）
+    #[structopt(long)]
+    include_class_resources: bool,
+
+    /// 检查每个class文件的SourceFile属性是否与对应的源文件名一致，不一致时发出警告
+    #[structopt(long)]
+    verify_source_attr: bool,
+
+    /// 在每个class文件的输出行中显示从class文件常量池读取的全限定类名
+    #[structopt(long)]
+    show_class_names: bool,
+
+    /// 遍历源代码目录的最大深度，直接位于源代码目录下的文件为第1层，默认不限制
+    #[structopt(long)]
+    source_max_depth: Option<usize>,
+
+    /// 在源文件对应的包目录下查找class文件的最大深度，直接位于包目录下的文件为第1层
+    #[structopt(long, default_value = "1")]
+    class_max_depth: usize,
+
+    /// 只复制修改时间不早于该时间的class文件（RFC 3339格式如 2024-05-01T12:00:00Z，或Unix时间戳）
+    #[structopt(long, parse(try_from_str = src_to_class::parse_timestamp))]
+    since: Option<SystemTime>,
+
+    /// 不复制源代码目录中的非Java文件，只输出class文件
+    #[structopt(long, alias = "exclude-non-java")]
+    no_resources: bool,
+
+    /// 同时把每个源文件复制到输出目录中与源代码目录相同的相对路径，生成附带源码的产物
+    #[structopt(long)]
+    copy_sources: bool,
+
+    /// 找不到class文件的源文件只发出警告并跳过，继续复制其他文件（默认中止操作）
+    #[structopt(long)]
+    allow_missing: bool,
+
+    /// 单个文件复制失败时记录错误并继续复制其他文件，结束时列出失败的文件并以非零状态退出
+    #[structopt(long)]
+    keep_going: bool,
+
+    /// 平均每个源文件的class文件数低于该值（如 1.2）时发出警告，用于发现只编译了部分源文件的构建
+    #[structopt(long)]
+    min_classes_per_source: Option<f64>,
+
+    /// 在汇总信息中列出class目录中没有对应源文件的class文件
+    #[structopt(long)]
+    report_orphans: bool,
+
+    /// 复制前检查是否有目标路径只有大小写不同（如foo.class和Foo.class），存在时列出并报错，避免部署到不区分大小写的文件系统时互相覆盖
+    #[structopt(long)]
+    case_check: bool,
+
+    /// 首次复制完成后持续监视源代码目录和class目录，文件变化时只重新复制受影响的文件
+    #[structopt(long, conflicts_with = "no-output-dir")]
+    watch: bool,
+
+    /// 日志格式：text为可读文本，json为每行一个JSON对象（便于日志平台采集），日志级别由RUST_LOG控制
+    #[structopt(long, default_value = "text", possible_values = &["text", "json"])]
+    log_format: LogFormat,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
 }
 
-/// Java类文件版本信息
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct JavaClassVersion {
-    major: u16,
-    minor: u16,
+/// 子命令，指定时不执行复制
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// 比较两个输出目录，列出新增、删除和修改的文件（先比较大小，再比较SHA-256）以及class文件JDK版本的变化
+    Compare {
+        /// 旧的输出目录
+        #[structopt(parse(from_os_str))]
+        old_dir: PathBuf,
+        /// 新的输出目录
+        #[structopt(parse(from_os_str))]
+        new_dir: PathBuf,
+    },
+    /// 打印class文件的主版本号、次版本号、对应的JDK版本以及是否使用了预览特性，不执行复制
+    Inspect {
+        /// 要检查的class文件，可指定多个
+        #[structopt(parse(from_os_str), required = true)]
+        class_files: Vec<PathBuf>,
+    },
+    /// 将指定shell的补全脚本输出到标准输出，如`source <(src_to_class completions bash)`
+    #[structopt(setting = AppSettings::Hidden)]
+    Completions {
+        /// shell类型
+        #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+        shell: Shell,
+    },
 }
 
-impl JavaClassVersion {
-    /// 返回人类可读的JDK版本字符串
-    fn to_jdk_version(&self) -> String {
-        match self.major {
-            45 => "JDK 1.1".to_string(),
-            46 => "JDK 1.2".to_string(),
-            47 => "JDK 1.3".to_string(),
-            48 => "JDK 1.4".to_string(),
-            49 => "JDK 5".to_string(),
-            50 => "JDK 6".to_string(),
-            51 => "JDK 7".to_string(),
-            52 => "JDK 8".to_string(),
-            53 => "JDK 9".to_string(),
-            54 => "JDK 10".to_string(),
-            55 => "JDK 11".to_string(),
-            56 => "JDK 12".to_string(),
-            57 => "JDK 13".to_string(),
-            58 => "JDK 14".to_string(),
-            59 => "JDK 15".to_string(),
-            60 => "JDK 16".to_string(),
-            61 => "JDK 17".to_string(),
-            62 => "JDK 18".to_string(),
-            63 => "JDK 19".to_string(),
-            64 => "JDK 20".to_string(),
-            65 => "JDK 21".to_string(),
-            _ => format!("未知JDK版本 (major: {})", self.major),
+/// 执行子命令
+fn run_command(command: Command) -> Result<()> {
+    match command {
+        Command::Compare { old_dir, new_dir } => {
+            src_to_class::compare_dirs(&old_dir, &new_dir)?.print();
+        }
+        Command::Inspect { class_files } => {
+            for class_file in &class_files {
+                let version = src_to_class::read_class_file_version(class_file)?;
+                println!("{}: 主版本号 {}，次版本号 {}，{}{}",
+                    class_file.display(),
+                    version.major,
+                    version.minor,
+                    version.to_jdk_version(),
+                    if version.is_preview() { "（预览特性）" } else { "" });
+            }
+        }
+        Command::Completions { shell } => {
+            Opt::clap().gen_completions_to("src_to_class", shell, &mut std::io::stdout());
         }
     }
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    env_logger::init();
-    let opt = Opt::from_args();
-    
-    // 检查路径是否存在
-    if !opt.source_dir.exists() {
-        bail!("源代码路径不存在: {:?}", opt.source_dir);
-    }
-    
-    if !opt.class_dir.exists() {
-        bail!("Class路径不存在: {:?}", opt.class_dir);
-    }
-    
-    // 创建输出目录（如果不存在）
-    if !opt.output_dir.exists() {
-        fs::create_dir_all(&opt.output_dir)?;
+/// 展开路径参数中的环境变量`$VAR`和`${VAR}`，引用的变量未设置时报错
+///
+/// `$`后面不是变量名时（如Windows共享路径中的`C$`）保留原样，不是有效UTF-8的路径不展开。
+fn expand_path(value: &OsStr) -> std::result::Result<PathBuf, OsString> {
+    let Some(value) = value.to_str() else {
+        return Ok(PathBuf::from(value));
+    };
+    expand_env_vars(value).map(PathBuf::from).map_err(OsString::from)
+}
+
+/// 展开字符串中的环境变量
+fn expand_env_vars(value: &str) -> std::result::Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        let after = &rest[index + 1..];
+        let (name, remaining) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}')
+                .ok_or_else(|| format!("路径中的环境变量缺少右括号: {}", value))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let variable = std::env::var(name)
+            .map_err(|_| format!("路径 {} 引用的环境变量 {} 未设置", value, name))?;
+        expanded.push_str(&variable);
+        rest = remaining;
     }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// 日志输出格式
+#[derive(Debug, Clone, Copy)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
     
-    // 收集所有源文件（包括Java和非Java文件）
-    let (java_files, non_java_files) = collect_source_files(&opt.source_dir)?;
-    info!("找到 {} 个Java源文件，{} 个非Java文件", java_files.len(), non_java_files.len());
-    
-    // 为每个源文件找到对应的class文件
-    let mut failed = false;
-    
-    // 记录源文件和对应的class文件
-    let mut source_to_classes: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
-    
-    for java_file in &java_files {
-        let java_rel_path = java_file.strip_prefix(&opt.source_dir)
-            .with_context(|| format!("无法获取相对路径: {:?}", java_file))?;
-        
-        let class_files = find_class_files(&opt.class_dir, java_rel_path)?;
-        
-        if class_files.is_empty() {
-            error!("找不到Java文件对应的class文件: {:?}", java_rel_path);
-            failed = true;
-            break;
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("无效的日志格式: {}，可选值为 text、json", value)),
         }
-        
-        source_to_classes.insert(java_rel_path.to_path_buf(), class_files);
     }
-    
-    // 如果有任何错误，不复制文件
-    if failed {
-        bail!("部分Java文件找不到对应的class文件，操作取消");
+}
+
+/// 初始化日志，输出到标准错误输出，未设置RUST_LOG时只输出错误
+///
+/// span结束时输出其耗时，`RUST_LOG=debug`时可以看到各阶段和每个源文件的查找耗时。
+fn init_logging(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(src_to_class::color_enabled(&std::io::stderr()))
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().with_current_span(true).with_span_list(true).init(),
     }
-    
-    // 用于记录所有class文件的JDK版本
-    let mut jdk_versions: HashMap<String, Vec<PathBuf>> = HashMap::new();
-    
-    // 首先复制非Java文件
-    println!("开始复制非Java文件...");
-    let mut copied_non_java_files = 0;
-    
-    for non_java_file in &non_java_files {
-        let rel_path = non_java_file.strip_prefix(&opt.source_dir)
-            .with_context(|| format!("无法获取相对路径: {:?}", non_java_file))?;
-        
-        let target_path = opt.output_dir.join(rel_path);
-        
-        // 确保目标目录存在
-        if let Some(parent) = target_path.parent() {
-            fs::create_dir_all(parent)?;
+}
+
+/// TOML配置文件的内容，键名与命令行参数对应
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    source_dir: Option<PathBuf>,
+    #[serde(default)]
+    class_dir: OneOrMany,
+    output_dir: Option<PathBuf>,
+    #[serde(default)]
+    includes: Vec<String>,
+    #[serde(default)]
+    excludes: Vec<String>,
+}
+
+/// 既可以写成单个值也可以写成数组的配置项
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Default for OneOrMany {
+    fn default() -> Self {
+        OneOrMany::Many(Vec::new())
+    }
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<PathBuf> {
+        match self {
+            OneOrMany::One(path) => vec![path],
+            OneOrMany::Many(paths) => paths,
         }
+    }
+}
+
+impl Config {
+    /// 读取配置文件，其中的相对路径相对于配置文件所在目录
+    fn load(path: &Path) -> Result<Config> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("无法读取配置文件: {:?}", path))?;
+        let mut config: Config = toml::from_str(&content)
+            .with_context(|| format!("解析配置文件失败: {:?}", path))?;
         
-        // 获取文件大小
-        let file_size = non_java_file.metadata()
-            .with_context(|| format!("无法获取文件元数据: {:?}", non_java_file))?.len();
-        
-        println!("非Java文件：{}，大小：{} 字节", rel_path.to_string_lossy(), file_size);
-        
-        // 复制文件
-        fs::copy(non_java_file, &target_path)
-            .with_context(|| format!("复制文件失败: {:?} -> {:?}", non_java_file, target_path))?;
-        
-        copied_non_java_files += 1;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        config.source_dir = config.source_dir.map(|dir| base_dir.join(dir));
+        config.output_dir = config.output_dir.map(|dir| base_dir.join(dir));
+        config.class_dir = OneOrMany::Many(config.class_dir.into_vec()
+            .into_iter()
+            .map(|dir| base_dir.join(dir))
+            .collect());
+        Ok(config)
     }
-    
-    if copied_non_java_files > 0 {
-        println!("----------------------------------------");
+}
+
+impl Opt {
+    /// 用按Maven/Gradle约定识别出的源代码目录、class目录和输出目录补全命令行中未指定的路径
+    fn merge_project_layout(&mut self, layout: src_to_class::ProjectLayout) {
+        if self.source_dir.is_none() {
+            self.source_dir = Some(layout.source_dir);
+        }
+        if self.class_dir.is_empty() && self.classpath_jars.is_empty() {
+            self.class_dir = vec![layout.class_dir];
+        }
+        if self.output_dir.is_none() {
+            self.output_dir = Some(layout.output_dir);
+        }
     }
     
-    // 复制所有class文件到输出目录并检查版本
-    println!("开始复制Java文件对应的class文件并检查JDK版本...");
-    
-    let mut copied_files = 0;
-    
-    for (java_rel_path, class_files) in &source_to_classes {
-        let java_file_name = java_rel_path.to_string_lossy();
-        println!("----------------------------------------");
-        
-        for class_file in class_files {
-            let rel_path = class_file.strip_prefix(&opt.class_dir)
-                .with_context(|| format!("无法获取相对路径: {:?}", class_file))?;
-            
-            let target_path = opt.output_dir.join(rel_path);
-            
-            // 确保目标目录存在
-            if let Some(parent) = target_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
-            
-            // 获取文件大小
-            let file_size = class_file.metadata()
-                .with_context(|| format!("无法获取文件元数据: {:?}", class_file))?.len();
-            
-            // 检查JDK版本
-            let jdk_version = match read_class_file_version(class_file) {
-                Ok(version) => {
-                    let v = version.to_jdk_version();
-                    
-                    // 记录版本信息
-                    jdk_versions.entry(v.clone())
-                        .or_insert_with(Vec::new)
-                        .push(class_file.clone());
-                    
-                    v
-                },
-                Err(err) => {
-                    eprintln!("  警告: 无法读取JDK版本: {}", err);
-                    "未知版本".to_string()
-                }
-            };
-            
-            // 打印详细信息
-            println!("源文件：{}，class文件：{}，大小：{} 字节，JDK版本：{}", 
-                java_file_name, 
-                rel_path.to_string_lossy(), 
-                file_size, 
-                jdk_version
-            );
-            
-            // 复制文件
-            fs::copy(class_file, &target_path)
-                .with_context(|| format!("复制文件失败: {:?} -> {:?}", class_file, target_path))?;
-            
-            copied_files += 1;
+    /// 用配置文件中的值补全命令行中未指定的参数
+    fn merge_config(&mut self, config: Config) {
+        if self.source_dir.is_none() {
+            self.source_dir = config.source_dir;
+        }
+        if self.class_dir.is_empty() {
+            self.class_dir = config.class_dir.into_vec();
+        }
+        if self.output_dir.is_none() {
+            self.output_dir = config.output_dir;
+        }
+        if self.includes.is_empty() {
+            self.includes = config.includes;
+        }
+        if self.excludes.is_empty() {
+            self.excludes = config.excludes;
         }
     }
-    println!("----------------------------------------");
     
-    // 打印汇总信息
-    println!("\n--- 汇总信息 ---");
-    println!("源文件总数: {}", source_to_classes.len());
-    println!("class文件总数: {}", copied_files);
-    println!("非Java文件总数: {}", copied_non_java_files);
-    println!("复制文件总计: {}", copied_files + copied_non_java_files);
-    
-    // 检查是否有不同的JDK版本
-    if jdk_versions.len() > 1 {
-        println!("\n-- 不同JDK版本文件统计 --");
-        for (version, files) in &jdk_versions {
-            println!("{}: {} 个文件", version, files.len());
+    /// 转换为库的复制选项
+    fn to_options(&self) -> Options {
+        Options {
+            dry_run: self.dry_run,
+            list_only: self.list_only,
+            audit: self.audit,
+            verify_only: self.verify_only,
+            atomic: self.atomic,
+            classes: self.classes.clone(),
+            skip_invalid_class: self.skip_invalid_class,
+            report: self.report.clone(),
+            manifest: self.manifest.clone(),
+            inventory: self.inventory.clone(),
+            expect: self.expect.clone(),
+            jobs: self.jobs,
+            read_jobs: self.read_jobs,
+            copy_jobs: self.copy_jobs,
+            target_jdk: self.target_jdk,
+            includes: self.includes.clone(),
+            excludes: self.excludes.clone(),
+            hardlink: self.hardlink,
+            symlink: self.symlink,
+            preserve_timestamps: self.preserve_timestamps,
+            checksums: self.checksums,
+            verbose: self.verbose,
+            quiet: self.quiet || self.summary_json,
+            only_jdk: self.only_jdk,
+            jar: self.jar.clone(),
+            jar_per_package: self.jar_per_package.then_some(self.jar_package_depth),
+            update_jar: self.update_jar.clone(),
+            no_output_dir: self.no_output_dir,
+            verify_hash: self.verify_hash,
+            follow_symlinks: self.follow_symlinks,
+            report_orphans: self.report_orphans,
+            case_check: self.case_check,
+            classpath_jars: self.classpath_jars.clone(),
+            force: self.force,
+            clean: self.clean,
+            bytes: self.bytes,
+            source_exts: self.source_exts.clone(),
+            copy_retries: self.copy_retries,
+            retry_delay_ms: self.retry_delay_ms,
+            buffer_size: self.buffer_size,
+            strip_debug: self.strip_debug,
+            tree: self.tree,
+            version_cache: self.version_cache.clone(),
+            map_packages: self.map_packages.clone(),
+            warnings_file: self.warnings_file.clone(),
+            include_class_resources: self.include_class_resources,
+            output_layout: self.output_layout,
+            normalize_eol: self.normalize_eol,
+            text_exts: self.text_exts.clone(),
+            verify_source_attr: self.verify_source_attr,
+            source_list: self.source_list.clone(),
+            mapping: self.mapping.clone(),
+            ignore_case: self.ignore_case,
+            show_class_names: self.show_class_names,
+            source_max_depth: self.source_max_depth,
+            class_max_depth: Some(self.class_max_depth),
+            since: self.since,
+            keep_going: self.keep_going,
+            multi_release: self.multi_release,
+            renames: self.renames.clone(),
+            min_classes_per_source: self.min_classes_per_source,
+            report_jsonl: self.report_jsonl.clone(),
+            skip_synthetic: self.skip_synthetic,
+            no_inner_classes: self.no_inner_classes,
+            guess_compiler: self.guess_compiler,
+            class_relative_to: self.class_relative_to.clone(),
+            allow_missing: self.allow_missing,
+            no_resources: self.no_resources,
+            copy_sources: self.copy_sources,
+            bucket_by_version: self.bucket_by_version,
+            fail_on_mixed_versions: self.fail_on_mixed_versions,
+            derive_package_from_source: self.derive_package_from_source,
+            duplicate_policy: if self.prefer_first {
+                DuplicatePolicy::PreferFirst
+            } else if self.prefer_last {
+                DuplicatePolicy::PreferLast
+            } else {
+                DuplicatePolicy::Error
+            },
         }
-        
-        warn!("警告: 检测到多个不同的JDK版本!");
-    } else if !jdk_versions.is_empty() {
-        let version = jdk_versions.keys().next().unwrap();
-        println!("所有文件JDK版本: {}", version);
     }
-    
-    info!("成功复制 {} 个class文件和 {} 个非Java文件到 {:?}", copied_files, copied_non_java_files, opt.output_dir);
-    Ok(())
 }
 
-/// 收集指定目录下的所有源文件，返回Java文件和非Java文件的列表
-fn collect_source_files(source_dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
-    let mut java_files = Vec::new();
-    let mut non_java_files = Vec::new();
+fn main() {
+    let opt = Opt::from_args();
+    init_logging(opt.log_format);
     
-    for entry in WalkDir::new(source_dir) {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.is_file() {
-            if path.extension().map_or(false, |ext| ext == "java") {
-                java_files.push(path.to_path_buf());
-            } else {
-                non_java_files.push(path.to_path_buf());
-            }
+    if let Err(err) = run(opt) {
+        if src_to_class::color_enabled(&std::io::stderr()) {
+            eprintln!("{} {:?}", "Error:".red().bold(), err);
+        } else {
+            eprintln!("Error: {:?}", err);
         }
+        process::exit(src_to_class::exit_code(&err));
     }
-    
-    Ok((java_files, non_java_files))
 }
 
-/// 查找Java文件对应的所有class文件
-fn find_class_files(class_dir: &Path, java_rel_path: &Path) -> Result<Vec<PathBuf>> {
-    let mut class_files = Vec::new();
-    
-    // 将Java路径转换为可能的class路径
-    let java_file_name = java_rel_path.file_stem()
-        .with_context(|| format!("无法获取文件名: {:?}", java_rel_path))?;
+fn run(mut opt: Opt) -> Result<()> {
+    if let Some(command) = opt.command.take() {
+        return run_command(command);
+    }
     
-    let package_path = java_rel_path.parent().unwrap_or(Path::new(""));
-    let class_dir_with_package = class_dir.join(package_path);
+    // 项目根目录推断的路径优先于配置文件
+    if let Some(project_root) = &opt.project_root {
+        let layout = src_to_class::detect_project_layout(project_root)?;
+        tracing::info!("识别为{}项目: {:?}", layout.build_system, project_root);
+        opt.merge_project_layout(layout);
+    }
     
-    // 如果类路径不存在，返回空列表
-    if !class_dir_with_package.exists() {
-        return Ok(vec![]);
+    if let Some(config_path) = &opt.config {
+        let config = Config::load(config_path)?;
+        opt.merge_config(config);
     }
     
-    let class_base_name = java_file_name.to_string_lossy();
+    let source_dir = opt.source_dir.as_deref()
+        .context("缺少源代码路径，请通过--source-dir或配置文件指定")?;
+    if opt.class_dir.is_empty() && opt.classpath_jars.is_empty() {
+        anyhow::bail!("缺少Class路径，请通过--class-dir、--classpath-jar或配置文件指定");
+    }
+    // 只校验时不使用输出目录
+    let output_dir = match opt.output_dir.as_deref() {
+        Some(output_dir) => output_dir,
+        None if opt.verify_only => Path::new(""),
+        None => anyhow::bail!("缺少输出目录，请通过--output-dir或配置文件指定"),
+    };
     
-    // 处理内部类的情况（查找所有BaseClass.class, BaseClass$1.class, BaseClass$InnerClass.class等）
-    for entry in WalkDir::new(&class_dir_with_package).max_depth(1) {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "class") {
-            let file_name = path.file_stem()
-                .with_context(|| format!("无法获取文件名: {:?}", path))?
-                .to_string_lossy();
-            
-            // 匹配主类或内部类
-            if file_name == class_base_name || file_name.starts_with(&format!("{}$", class_base_name)) {
-                class_files.push(path.to_path_buf());
-            }
+    let options = opt.to_options();
+    let summary = src_to_class::run(source_dir, &opt.class_dir, output_dir, &options)?;
+    if opt.summary_json {
+        println!("{}", summary.to_json()?);
+    } else if !opt.list_only {
+        if opt.audit || opt.verify_only {
+            summary.print_audit();
+        } else {
+            summary.print();
+        }
+        if opt.group_report {
+            summary.print_group_report(opt.group_depth);
+        }
+        if opt.histogram {
+            summary.print_histogram();
+        }
+        if opt.timings {
+            summary.print_timings();
         }
     }
-    
-    Ok(class_files)
-}
-
-/// 读取class文件的版本信息
-fn read_class_file_version(path: &Path) -> Result<JavaClassVersion> {
-    // 打开文件
-    let mut file = fs::File::open(path)
-        .with_context(|| format!("无法打开class文件: {:?}", path))?;
-    
-    // 读取前8个字节
-    let mut buffer = [0u8; 8];
-    file.read_exact(&mut buffer)
-        .with_context(|| format!("无法读取class文件头: {:?}", path))?;
-    
-    // 检查魔数 (0xCAFEBABE)
-    if buffer[0] != 0xCA || buffer[1] != 0xFE || buffer[2] != 0xBA || buffer[3] != 0xBE {
-        bail!("无效的class文件格式，魔数不匹配: {:?}", path);
+    if opt.warnings_file.is_none() {
+        src_to_class::print_warnings(&summary.warnings);
     }
-    
-    // 读取次版本号和主版本号
-    let minor = ((buffer[4] as u16) << 8) | (buffer[5] as u16);
-    let major = ((buffer[6] as u16) << 8) | (buffer[7] as u16);
-    
-    Ok(JavaClassVersion { major, minor })
+    if !summary.failures.is_empty() {
+        return Err(src_to_class::Error::CopyFailures { count: summary.failures.len() }.into());
+    }
+    if opt.watch {
+        src_to_class::watch(source_dir, &opt.class_dir, output_dir, &options, &summary)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,91 @@
+//! 复制进度条：显示已处理的文件数、复制吞吐量和预计剩余时间
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use anyhow::{Result, Context};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use crate::format_size;
+
+/// 进度条每秒最多重绘的次数，避免频繁刷新造成闪烁
+const REFRESH_RATE: u8 = 4;
+
+/// 重新计算吞吐量的最短间隔
+const THROUGHPUT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 新采样在滚动吞吐量中所占的权重，越大越灵敏，越小越平稳
+const THROUGHPUT_WEIGHT: f64 = 0.3;
+
+/// 复制进度条，按文件数推进，另外统计已复制的字节数以计算吞吐量
+pub(crate) struct CopyProgress {
+    bar: ProgressBar,
+    /// 累计复制的字节数
+    bytes: AtomicU64,
+    /// 上次计算吞吐量时的状态
+    sample: Mutex<ThroughputSample>,
+}
+
+/// 吞吐量采样
+struct ThroughputSample {
+    time: Instant,
+    bytes: u64,
+    /// 滚动吞吐量（字节/秒），尚未采样时为None
+    rate: Option<f64>,
+}
+
+impl CopyProgress {
+    /// 创建共有`total`个文件的进度条
+    pub(crate) fn new(total: u64, prefix: &str) -> Result<Self> {
+        let bar = ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::stderr_with_hz(REFRESH_RATE));
+        bar.set_message("-- /s");
+        bar.set_style(ProgressStyle::with_template("{prefix}[{bar:40}] {pos}/{len} {msg}，已用 {elapsed}，预计剩余 {eta}")
+            .context("无效的进度条模板")?
+            .progress_chars("=> "));
+        bar.set_prefix(prefix.to_string());
+        Ok(CopyProgress {
+            bar,
+            bytes: AtomicU64::new(0),
+            sample: Mutex::new(ThroughputSample {
+                time: Instant::now(),
+                bytes: 0,
+                rate: None,
+            }),
+        })
+    }
+
+    /// 推进`delta`个文件（包括跳过和失败的文件）
+    pub(crate) fn inc(&self, delta: u64) {
+        self.bar.inc(delta);
+    }
+
+    /// 记录复制了`bytes`字节，距上次采样超过间隔时更新显示的吞吐量
+    pub(crate) fn add_bytes(&self, bytes: u64) {
+        let total = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        // 其他线程正在更新时直接跳过，下次再更新
+        let Ok(mut sample) = self.sample.try_lock() else {
+            return;
+        };
+        let now = Instant::now();
+        let elapsed = now.duration_since(sample.time);
+        if elapsed < THROUGHPUT_INTERVAL {
+            return;
+        }
+        let current = total.saturating_sub(sample.bytes) as f64 / elapsed.as_secs_f64();
+        let rate = match sample.rate {
+            Some(rate) => rate + THROUGHPUT_WEIGHT * (current - rate),
+            None => current,
+        };
+        *sample = ThroughputSample { time: now, bytes: total, rate: Some(rate) };
+        self.bar.set_message(format!("{}/s", format_size(rate as u64)));
+    }
+
+    /// 在进度条上方打印一行
+    pub(crate) fn println(&self, line: &str) {
+        self.bar.println(line);
+    }
+
+    /// 清除进度条
+    pub(crate) fn finish_and_clear(&self) {
+        self.bar.finish_and_clear();
+    }
+}
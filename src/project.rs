@@ -0,0 +1,75 @@
+//! 按Maven和Gradle的目录约定识别项目根目录下的源代码目录、class目录和输出目录
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Result};
+
+/// 构建工具
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildSystem {
+    Maven,
+    Gradle,
+}
+
+impl BuildSystem {
+    /// 该构建工具约定的源代码目录、class目录、构建输出目录（均相对于项目根目录）和构建文件
+    fn convention(self) -> (&'static str, &'static str, &'static str, &'static [&'static str]) {
+        match self {
+            BuildSystem::Maven => ("src/main/java", "target/classes", "target", &["pom.xml"]),
+            BuildSystem::Gradle => ("src/main/java", "build/classes/java/main", "build", &["build.gradle", "build.gradle.kts"]),
+        }
+    }
+}
+
+impl fmt::Display for BuildSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildSystem::Maven => write!(f, "Maven"),
+            BuildSystem::Gradle => write!(f, "Gradle"),
+        }
+    }
+}
+
+/// 识别出的项目目录
+#[derive(Debug, Clone)]
+pub struct ProjectLayout {
+    pub build_system: BuildSystem,
+    pub source_dir: PathBuf,
+    pub class_dir: PathBuf,
+    /// 构建输出目录下的`src_to_class`目录
+    pub output_dir: PathBuf,
+}
+
+/// 输出目录在构建输出目录下的名称
+const OUTPUT_DIR_NAME: &str = "src_to_class";
+
+/// 识别项目根目录使用的目录约定，源代码目录和class目录都存在才算匹配
+///
+/// 两种约定都匹配时，按根目录下的构建文件（`pom.xml`或`build.gradle(.kts)`）选择，都没有时使用Maven。
+pub fn detect_project_layout(root: &Path) -> Result<ProjectLayout> {
+    if !root.is_dir() {
+        bail!("项目根目录不存在: {:?}", root);
+    }
+    let matched: Vec<BuildSystem> = [BuildSystem::Maven, BuildSystem::Gradle].into_iter()
+        .filter(|build_system| {
+            let (source_dir, class_dir, _, _) = build_system.convention();
+            root.join(source_dir).is_dir() && root.join(class_dir).is_dir()
+        })
+        .collect();
+    let build_system = match matched[..] {
+        [] => bail!("无法识别项目目录结构: {:?}，需要Maven（src/main/java和target/classes）或Gradle（src/main/java和build/classes/java/main）的目录，请先编译项目或通过--source-dir、--class-dir指定", root),
+        [build_system] => build_system,
+        _ => matched.iter()
+            .copied()
+            .find(|build_system| build_system.convention().3.iter().any(|file| root.join(file).is_file()))
+            .unwrap_or(BuildSystem::Maven),
+    };
+
+    let (source_dir, class_dir, build_dir, _) = build_system.convention();
+    Ok(ProjectLayout {
+        build_system,
+        source_dir: root.join(source_dir),
+        class_dir: root.join(class_dir),
+        output_dir: root.join(build_dir).join(OUTPUT_DIR_NAME),
+    })
+}
@@ -0,0 +1,127 @@
+//! 复制过程中收集的警告
+
+use std::path::Path;
+use std::fs;
+use std::collections::BTreeMap;
+use anyhow::{Result, Context};
+use serde::Serialize;
+use owo_colors::OwoColorize;
+use crate::color_enabled;
+
+/// 警告的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum WarningKind {
+    /// 无法读取class文件的JDK版本
+    MissingVersion,
+    /// class文件使用了多个不同的JDK版本
+    VersionMismatch,
+    /// class文件使用了预览特性编译
+    PreviewFeature,
+    /// class文件声明的包名与源文件路径不一致
+    PackageMismatch,
+    /// 无法读取class文件声明的类名
+    UnreadableClassName,
+    /// 同一个class文件存在于多个class目录或jar中
+    Shadowed,
+    /// 源文件的class文件不全在期望的包目录下
+    DivergentPackage,
+    /// 同一个简单类名对应多个目录中的class文件
+    DuplicateSimpleName,
+    /// 包映射规则没有匹配任何文件
+    UnmatchedMapping,
+    /// 平铺输出或重命名后多个class文件使用相同的输出路径
+    NameCollision,
+    /// class文件的SourceFile属性与源文件名不一致
+    SourceFileMismatch,
+    /// 忽略大小写找到的class文件名与类型名大小写不一致
+    CaseMismatch,
+    /// 平均每个源文件的class文件数低于--min-classes-per-source
+    LowClassRatio,
+    /// 使用--allow-missing时找不到class文件的源文件
+    MissingClasses,
+    /// 已复制的包中有内部类的主类不在选择的源文件中
+    MissingDependency,
+    /// 使用--hardlink时无法创建硬链接而改为复制
+    HardlinkFallback,
+    /// 使用--symlink时输出目录中是指向原文件的符号链接
+    SymlinkOutput,
+}
+
+impl WarningKind {
+    /// 分组打印时使用的标题
+    pub fn label(&self) -> &'static str {
+        match self {
+            WarningKind::MissingVersion => "无法读取JDK版本",
+            WarningKind::VersionMismatch => "JDK版本不一致",
+            WarningKind::PreviewFeature => "使用预览特性编译",
+            WarningKind::PackageMismatch => "包名与源文件路径不一致",
+            WarningKind::UnreadableClassName => "无法读取类名",
+            WarningKind::Shadowed => "class文件被覆盖",
+            WarningKind::DivergentPackage => "class文件不在期望的包目录下",
+            WarningKind::DuplicateSimpleName => "类名对应多个目录",
+            WarningKind::UnmatchedMapping => "包映射未匹配",
+            WarningKind::NameCollision => "输出文件名冲突",
+            WarningKind::SourceFileMismatch => "SourceFile属性与源文件不一致",
+            WarningKind::CaseMismatch => "class文件名大小写不一致",
+            WarningKind::LowClassRatio => "class文件数量过少",
+            WarningKind::MissingClasses => "找不到class文件",
+            WarningKind::MissingDependency => "可能缺失的依赖class",
+            WarningKind::HardlinkFallback => "硬链接改为复制",
+            WarningKind::SymlinkOutput => "输出为符号链接",
+        }
+    }
+}
+
+/// 一条警告
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// 警告涉及的文件或包的路径，与具体文件无关时为None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(kind: WarningKind, path: Option<&Path>, message: String) -> Warning {
+        Warning {
+            kind,
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+            message,
+        }
+    }
+}
+
+/// 按类型分组将警告打印到标准错误输出，输出到终端时使用黄色
+pub fn print_warnings(warnings: &[Warning]) {
+    let color = color_enabled(&std::io::stderr());
+    let mut groups: BTreeMap<WarningKind, Vec<&Warning>> = BTreeMap::new();
+    for warning in warnings {
+        groups.entry(warning.kind).or_default().push(warning);
+    }
+
+    for (kind, warnings) in groups {
+        let title = format!("-- 警告: {}（{} 条） --", kind.label(), warnings.len());
+        if color {
+            eprintln!("\n{}", title.yellow().bold());
+        } else {
+            eprintln!("\n{}", title);
+        }
+        for warning in warnings {
+            if color {
+                eprintln!("{}", warning.message.yellow());
+            } else {
+                eprintln!("{}", warning.message);
+            }
+        }
+    }
+}
+
+/// 将警告以格式化的JSON写入指定文件
+pub(crate) fn write_warnings(path: &Path, warnings: &[Warning]) -> Result<()> {
+    let json = serde_json::to_string_pretty(warnings)
+        .context("序列化警告失败")?;
+    fs::write(path, json)
+        .with_context(|| format!("写入警告文件失败: {:?}", path))?;
+    Ok(())
+}
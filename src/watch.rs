@@ -0,0 +1,145 @@
+//! 监视源代码目录和class目录，文件变化后只重新复制受影响的源文件
+
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+use anyhow::{Result, Context};
+use tracing::{debug, error, warn};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use crate::{run_with_sources, print_warnings, LookupOptions, Options, Summary};
+
+/// 合并连续文件变化事件的等待时间
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 首次复制完成后持续监视`source_dir`和`class_dirs`，直到进程被终止
+///
+/// 源文件变化时重新复制该源文件，class文件变化时重新复制其对应的源文件。
+/// 重新复制时允许写入非空的输出目录，不写入报告和清单；出错时只打印错误并继续监视。
+pub fn watch(source_dir: &Path, class_dirs: &[PathBuf], output_dir: &Path, options: &Options, initial: &Summary) -> Result<()> {
+    let lookup = options.lookup_options();
+    let watched_source_dir = fs::canonicalize(source_dir)
+        .with_context(|| format!("无法解析路径: {:?}", source_dir))?;
+    let watched_class_dirs = class_dirs.iter()
+        .map(|dir| fs::canonicalize(dir).with_context(|| format!("无法解析路径: {:?}", dir)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let rerun_options = Options {
+        force: true,
+        clean: false,
+        atomic: false,
+        report: None,
+        report_jsonl: None,
+        manifest: None,
+        inventory: None,
+        expect: None,
+        update_jar: None,
+        tree: false,
+        warnings_file: None,
+        source_list: None,
+        report_orphans: false,
+        ..options.clone()
+    };
+
+    // 复制时读取文件也会产生事件，只有修改时间变化的文件才视为变化
+    let started = SystemTime::now();
+    let mut modified_times: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let (sender, receiver) = mpsc::channel();
+    let mut debouncer = new_debouncer(DEBOUNCE, sender).context("创建文件监视器失败")?;
+    for dir in std::iter::once(&watched_source_dir).chain(&watched_class_dirs) {
+        debouncer.watcher()
+            .watch(dir, RecursiveMode::Recursive)
+            .with_context(|| format!("无法监视目录: {:?}", dir))?;
+    }
+
+    let mut class_to_source = HashMap::new();
+    update_class_index(&mut class_to_source, initial);
+    println!("正在监视源代码目录和class目录的变化，按Ctrl+C退出");
+
+    for result in receiver {
+        let events = match result {
+            Ok(events) => events,
+            Err(err) => {
+                warn!("文件监视出错: {}", err);
+                continue;
+            }
+        };
+
+        // 被删除的文件不再复制，输出目录中已有的文件保留
+        let mut sources = BTreeSet::new();
+        for event in &events {
+            let Some(modified) = fs::metadata(&event.path).ok()
+                .filter(|metadata| metadata.is_file())
+                .and_then(|metadata| metadata.modified().ok()) else {
+                continue;
+            };
+            if !modified_time_changed(&mut modified_times, &event.path, modified, started) {
+                continue;
+            }
+            let rel_source = if let Ok(rel_path) = event.path.strip_prefix(&watched_source_dir) {
+                Some(rel_path.to_path_buf())
+            } else {
+                watched_class_dirs.iter()
+                    .find_map(|dir| event.path.strip_prefix(dir).ok())
+                    .filter(|rel_path| rel_path.extension().is_some_and(|ext| ext == "class"))
+                    .and_then(|rel_path| class_to_source.get(rel_path).cloned()
+                        .or_else(|| guess_source(source_dir, rel_path, &lookup)))
+            };
+            match rel_source {
+                Some(rel_source) => {
+                    sources.insert(source_dir.join(rel_source));
+                }
+                None => debug!("忽略与源文件无关的变化: {:?}", event.path),
+            }
+        }
+        if sources.is_empty() {
+            continue;
+        }
+
+        let sources: Vec<PathBuf> = sources.into_iter().collect();
+        match run_with_sources(source_dir, class_dirs, output_dir, &rerun_options, Some(&sources)) {
+            Ok(summary) => {
+                update_class_index(&mut class_to_source, &summary);
+                println!("重新复制 {} 个文件", summary.total_copied() - summary.unchanged_count);
+                print_warnings(&summary.warnings);
+            }
+            Err(err) => error!("重新复制失败: {:?}", err),
+        }
+    }
+    Ok(())
+}
+
+/// 记录文件的修改时间，返回与上次记录相比是否变化，首次出现的文件在监视开始后修改过才视为变化
+fn modified_time_changed(modified_times: &mut HashMap<PathBuf, SystemTime>, path: &Path, modified: SystemTime, started: SystemTime) -> bool {
+    match modified_times.insert(path.to_path_buf(), modified) {
+        Some(previous) => previous != modified,
+        None => modified >= started,
+    }
+}
+
+/// 记录每个class文件（相对于class目录）对应的源文件（相对于源代码目录）
+fn update_class_index(class_to_source: &mut HashMap<PathBuf, PathBuf>, summary: &Summary) {
+    for source in &summary.sources {
+        for class in &source.classes {
+            class_to_source.insert(PathBuf::from(&class.path), PathBuf::from(&source.source));
+        }
+    }
+}
+
+/// 为尚未复制过的class文件推断源文件：同一包目录下与外部类同名的源文件
+fn guess_source(source_dir: &Path, class_rel_path: &Path, lookup: &LookupOptions) -> Option<PathBuf> {
+    let stem = class_rel_path.file_stem()?.to_str()?;
+    let outer_name = stem.split('$').next()?;
+    let package_path = class_rel_path.parent().unwrap_or(Path::new(""));
+
+    let mut names = vec![outer_name];
+    if let Some(facade_name) = outer_name.strip_suffix("Kt") {
+        names.push(facade_name);
+    }
+    let extensions = std::iter::once("java")
+        .chain(lookup.source_exts.iter().map(|ext| ext.trim_start_matches('.')));
+    extensions
+        .flat_map(|ext| names.iter().map(move |name| package_path.join(format!("{}.{}", name, ext))))
+        .find(|rel_path| source_dir.join(rel_path).is_file())
+}